@@ -1,5 +1,25 @@
 import init, { Driftwave as WasmDriftwave } from '../wasm/driftwave_web.js';
 
+type PlaybackState = 'playing' | 'paused' | 'finished' | 'stopped' | 'invalid';
+
+export type PlayerErrorKind =
+  | 'NotInitialized'
+  | 'FileNotFound'
+  | 'UnsupportedFormat'
+  | 'DecodeFailed'
+  | 'InvalidRange'
+  | 'InvalidArgument'
+  | 'InvalidState'
+  | 'DeviceUnavailable'
+  | 'BackendSpecific';
+
+// Thrown by playback methods and passed to 'error' listeners
+export interface PlayerError extends Error {
+  name: PlayerErrorKind;
+  // The backend's numeric code, for 'BackendSpecific' only
+  code?: number;
+}
+
 interface Metadata {
   sampleRate: number;
   channelCount: number;
@@ -16,6 +36,8 @@ class Driftwave {
     await init();
     const instance = new Driftwave();
     instance.wasm = new WasmDriftwave();
+    // Playback events from Rust: 'timeupdate' (frame), 'finish', 'loop', 'error' (Error)
+    instance.wasm.set_listener((event: string, value?: unknown) => instance.emit(event, value));
     return instance;
   }
 
@@ -26,11 +48,28 @@ class Driftwave {
       await this.wasm.load_async(url);
       this.emit('ready');
     } catch (error) {
-      this.emit('error', error);
+      this.emit('error', error as PlayerError);
+      throw error;
+    }
+  }
+
+  // Decodes an encoded audio file already in memory; `buffer` is detached
+  async loadArrayBuffer(buffer: ArrayBuffer): Promise<void> {
+    if (!this.wasm) return;
+
+    try {
+      await this.wasm.load_array_buffer(buffer);
+      this.emit('ready');
+    } catch (error) {
+      this.emit('error', error as PlayerError);
       throw error;
     }
   }
 
+  async loadBlob(blob: Blob): Promise<void> {
+    return this.loadArrayBuffer(await blob.arrayBuffer());
+  }
+
   play(): void {
     if (!this.wasm) return;
     this.wasm.play();
@@ -49,6 +88,12 @@ class Driftwave {
     this.emit('play');
   }
 
+  playLoop(loopStart: number, loopEnd: number, startFrame: number = loopStart): void {
+    if (!this.wasm) return;
+    this.wasm.play_loop(loopStart, loopEnd, startFrame);
+    this.emit('play');
+  }
+
   pause(): number | null {
     if (!this.wasm) return null;
     const frame = this.wasm.pause();
@@ -56,11 +101,54 @@ class Driftwave {
     return frame;
   }
 
+  resume(): void {
+    if (!this.wasm) return;
+    this.wasm.resume();
+    this.emit('play');
+  }
+
+  seek(frame: number): void {
+    if (!this.wasm) return;
+    this.wasm.seek(frame);
+    this.emit('seek', frame);
+  }
+
+  setVolume(volume: number): void {
+    if (!this.wasm) return;
+    this.wasm.set_volume(volume);
+  }
+
+  setVolumeDb(db: number): void {
+    if (!this.wasm) return;
+    this.wasm.set_volume_db(db);
+  }
+
+  setMuted(muted: boolean): void {
+    if (!this.wasm) return;
+    this.wasm.set_muted(muted);
+  }
+
+  setPan(pan: number): void {
+    if (!this.wasm) return;
+    this.wasm.set_pan(pan);
+  }
+
+  // Positions stay in source frames at any rate
+  setPlaybackRate(rate: number, preservePitch: boolean = false): void {
+    if (!this.wasm) return;
+    this.wasm.set_playback_rate(rate, preservePitch);
+  }
+
   isPlaying(): boolean {
     if (!this.wasm) return false;
     return this.wasm.is_playing();
   }
 
+  getState(): PlaybackState {
+    if (!this.wasm) return 'invalid';
+    return this.wasm.get_state() as PlaybackState;
+  }
+
   getMetadata(): Metadata | null {
     if (!this.wasm) return null;
     try {
@@ -0,0 +1,513 @@
+use crate::peakfile::PeaksFileError;
+use crate::peaks::{Peak, PeakPyramid};
+
+use serde_json::{Value, json};
+use std::io::{BufReader, BufWriter, Read, Write};
+
+const FLAG_8_BIT: u32 = 1;
+
+// audiowaveform itself writes at most 24 channels
+const MAX_CHANNELS: usize = 24;
+
+/// Peaks read from a file produced by BBC's `audiowaveform`, as served to Peaks.js.
+///
+/// Both the `.dat` and `.json` formats hold one min/max pair per channel for
+/// every `samples_per_pixel` frames, quantized to 8 or 16 bits. Imported data
+/// becomes level 0 of a [`PeakPyramid`]; exports are written from any level.
+#[derive(Debug, Clone)]
+pub struct Audiowaveform {
+    pub sample_rate: u32,
+    /// Quantization of the source file, 8 or 16.
+    pub bits: u8,
+    pub peaks: PeakPyramid,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExportOptions {
+    /// Format version: 1 (mono only) or 2.
+    pub version: u32,
+    /// 8 or 16.
+    pub bits: u8,
+    /// Pyramid level to export; `samples_per_pixel` is that level's bin size.
+    pub level: usize,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            version: 2,
+            bits: 8,
+            level: 0,
+        }
+    }
+}
+
+impl Audiowaveform {
+    /// Reads the binary `.dat` format, version 1 or 2.
+    pub fn read_dat<R: Read>(reader: R) -> Result<Self, PeaksFileError> {
+        let mut r = BufReader::new(reader);
+        let version = read_i32(&mut r)?;
+        if version != 1 && version != 2 {
+            return Err(PeaksFileError::UnsupportedVersion(version.into()));
+        }
+        let flags = read_i32(&mut r)? as u32;
+        let sample_rate = read_i32(&mut r)?;
+        let samples_per_pixel = read_i32(&mut r)?;
+        let length = read_i32(&mut r)? as u32;
+        let channels = if version == 2 { read_i32(&mut r)? } else { 1 };
+        let bits = if flags & FLAG_8_BIT != 0 { 8 } else { 16 };
+
+        let header = Header::new(sample_rate, samples_per_pixel, channels, bits)?;
+        // Grow as values arrive so a corrupt length cannot force a huge allocation
+        let count = (length as usize).saturating_mul(header.channels * 2);
+        let mut values = Vec::with_capacity(count.min(1 << 20));
+        for _ in 0..count {
+            let value = if bits == 8 {
+                let mut byte = [0u8; 1];
+                r.read_exact(&mut byte)?;
+                byte[0] as i8 as i32
+            } else {
+                let mut bytes = [0u8; 2];
+                r.read_exact(&mut bytes)?;
+                i16::from_le_bytes(bytes) as i32
+            };
+            values.push(value);
+        }
+        Ok(header.build(&values))
+    }
+
+    /// Reads the `.json` format, with or without a `version` field.
+    pub fn read_json<R: Read>(reader: R) -> Result<Self, PeaksFileError> {
+        let value: Value = serde_json::from_reader(BufReader::new(reader))
+            .map_err(|e| PeaksFileError::InvalidFormat(e.to_string()))?;
+
+        let field = |name: &str| {
+            value.get(name).and_then(Value::as_i64).ok_or_else(|| {
+                PeaksFileError::InvalidFormat(format!("missing or non-integer '{}'", name))
+            })
+        };
+        let int = |name: &str, value: i64| {
+            i32::try_from(value).map_err(|_| {
+                PeaksFileError::InvalidFormat(format!("'{}' out of range: {}", name, value))
+            })
+        };
+        let version = value.get("version").and_then(Value::as_i64).unwrap_or(1);
+        if version != 1 && version != 2 {
+            return Err(PeaksFileError::UnsupportedVersion(version));
+        }
+        let channels = value.get("channels").and_then(Value::as_i64).unwrap_or(1);
+        let header = Header::new(
+            int("sample_rate", field("sample_rate")?)?,
+            int("samples_per_pixel", field("samples_per_pixel")?)?,
+            int("channels", channels)?,
+            field("bits")?,
+        )?;
+
+        let data = value
+            .get("data")
+            .and_then(Value::as_array)
+            .ok_or_else(|| PeaksFileError::InvalidFormat("missing 'data' array".to_string()))?;
+        let values = data
+            .iter()
+            .map(|v| {
+                let v = v.as_i64().ok_or_else(|| {
+                    PeaksFileError::InvalidFormat("non-integer value in 'data'".to_string())
+                })?;
+                int("data", v)
+            })
+            .collect::<Result<Vec<i32>, _>>()?;
+        if values.len() % (header.channels * 2) != 0 {
+            return Err(PeaksFileError::InvalidFormat(
+                "'data' length is not a whole number of min/max pairs".to_string(),
+            ));
+        }
+        Ok(header.build(&values))
+    }
+
+    /// Writes `peaks` in the binary `.dat` format.
+    pub fn write_dat<W: Write>(
+        writer: W,
+        peaks: &PeakPyramid,
+        sample_rate: u32,
+        options: &ExportOptions,
+    ) -> Result<(), PeaksFileError> {
+        let export = Export::new(peaks, options)?;
+        let mut w = BufWriter::new(writer);
+        let flags = if options.bits == 8 { FLAG_8_BIT } else { 0 };
+        w.write_all(&(options.version as i32).to_le_bytes())?;
+        w.write_all(&flags.to_le_bytes())?;
+        w.write_all(&(sample_rate as i32).to_le_bytes())?;
+        w.write_all(&(export.samples_per_pixel as i32).to_le_bytes())?;
+        w.write_all(&(export.length as u32).to_le_bytes())?;
+        if options.version == 2 {
+            w.write_all(&(peaks.channel_count() as i32).to_le_bytes())?;
+        }
+        for value in export.values() {
+            if options.bits == 8 {
+                w.write_all(&(value as i8).to_le_bytes())?;
+            } else {
+                w.write_all(&(value as i16).to_le_bytes())?;
+            }
+        }
+        w.flush()?;
+        Ok(())
+    }
+
+    /// Writes `peaks` in the `.json` format.
+    pub fn write_json<W: Write>(
+        writer: W,
+        peaks: &PeakPyramid,
+        sample_rate: u32,
+        options: &ExportOptions,
+    ) -> Result<(), PeaksFileError> {
+        let export = Export::new(peaks, options)?;
+        let data: Vec<i32> = export.values().collect();
+        let mut value = json!({
+            "version": options.version,
+            "sample_rate": sample_rate,
+            "samples_per_pixel": export.samples_per_pixel,
+            "bits": options.bits,
+            "length": export.length,
+            "data": data,
+        });
+        if options.version == 2 {
+            value["channels"] = json!(peaks.channel_count());
+        }
+        let mut w = BufWriter::new(writer);
+        serde_json::to_writer(&mut w, &value)
+            .map_err(|e| PeaksFileError::InvalidFormat(e.to_string()))?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+struct Header {
+    sample_rate: u32,
+    samples_per_pixel: u64,
+    channels: usize,
+    bits: u8,
+}
+
+impl Header {
+    fn new(
+        sample_rate: i32,
+        samples_per_pixel: i32,
+        channels: i32,
+        bits: i64,
+    ) -> Result<Self, PeaksFileError> {
+        if sample_rate <= 0 || samples_per_pixel <= 0 || channels <= 0 {
+            return Err(PeaksFileError::InvalidFormat(
+                "sample rate, samples per pixel and channels must be positive".to_string(),
+            ));
+        }
+        if channels as usize > MAX_CHANNELS {
+            return Err(PeaksFileError::InvalidFormat(format!(
+                "{} channels, at most {} are supported",
+                channels, MAX_CHANNELS
+            )));
+        }
+        if bits != 8 && bits != 16 {
+            return Err(PeaksFileError::InvalidFormat(format!(
+                "{} bits per value",
+                bits
+            )));
+        }
+        Ok(Header {
+            sample_rate: sample_rate as u32,
+            samples_per_pixel: samples_per_pixel as u64,
+            channels: channels as usize,
+            bits: bits as u8,
+        })
+    }
+
+    // `values` holds min/max pairs interleaved by channel within each pixel.
+    fn build(&self, values: &[i32]) -> Audiowaveform {
+        let scale = full_scale(self.bits);
+        let mut channels = vec![Vec::new(); self.channels];
+        for pixel in values.chunks_exact(self.channels * 2) {
+            for (bins, pair) in channels.iter_mut().zip(pixel.chunks_exact(2)) {
+                bins.push(Peak {
+                    min: pair[0] as f32 / scale,
+                    max: pair[1] as f32 / scale,
+                });
+            }
+        }
+        Audiowaveform {
+            sample_rate: self.sample_rate,
+            bits: self.bits,
+            peaks: PeakPyramid::from_base_bins(self.samples_per_pixel, channels),
+        }
+    }
+}
+
+struct Export<'a> {
+    peaks: &'a PeakPyramid,
+    samples_per_pixel: u64,
+    length: u64,
+    scale: f32,
+    min: f32,
+    max: f32,
+}
+
+impl<'a> Export<'a> {
+    fn new(peaks: &'a PeakPyramid, options: &ExportOptions) -> Result<Self, PeaksFileError> {
+        if options.version != 1 && options.version != 2 {
+            return Err(PeaksFileError::UnsupportedVersion(options.version.into()));
+        }
+        if options.version == 1 && peaks.channel_count() != 1 {
+            return Err(PeaksFileError::InvalidFormat(
+                "version 1 holds a single channel".to_string(),
+            ));
+        }
+        if peaks.channel_count() > MAX_CHANNELS {
+            return Err(PeaksFileError::InvalidFormat(format!(
+                "{} channels, at most {} are supported",
+                peaks.channel_count(),
+                MAX_CHANNELS
+            )));
+        }
+        if options.bits != 8 && options.bits != 16 {
+            return Err(PeaksFileError::InvalidFormat(format!(
+                "{} bits per value",
+                options.bits
+            )));
+        }
+        let samples_per_pixel = peaks.bin_frames(options.level);
+        if samples_per_pixel > i32::MAX as u64 {
+            return Err(PeaksFileError::InvalidFormat(
+                "level too coarse for the format".to_string(),
+            ));
+        }
+        let scale = full_scale(options.bits);
+        Ok(Export {
+            peaks,
+            samples_per_pixel,
+            // audiowaveform keeps a trailing partial pixel
+            length: peaks.frame_count().div_ceil(samples_per_pixel),
+            scale,
+            min: -scale,
+            max: scale - 1.0,
+        })
+    }
+
+    fn values(&self) -> impl Iterator<Item = i32> + '_ {
+        (0..self.length).flat_map(move |pixel| {
+            let start = pixel * self.samples_per_pixel;
+            let end = start + self.samples_per_pixel;
+            (0..self.peaks.channel_count()).flat_map(move |channel| {
+                let peak = self.peaks.summary(channel, start, end);
+                let (min, max) = if peak.is_empty() {
+                    (0.0, 0.0)
+                } else {
+                    (peak.min, peak.max)
+                };
+                [self.quantize(min), self.quantize(max)]
+            })
+        })
+    }
+
+    fn quantize(&self, sample: f32) -> i32 {
+        (sample * self.scale).round().clamp(self.min, self.max) as i32
+    }
+}
+
+fn full_scale(bits: u8) -> f32 {
+    if bits == 8 { 128.0 } else { 32768.0 }
+}
+
+fn read_i32<R: Read>(r: &mut R) -> Result<i32, PeaksFileError> {
+    let mut bytes = [0u8; 4];
+    r.read_exact(&mut bytes)?;
+    Ok(i32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Eighths quantize exactly at both 8 and 16 bits
+    fn pyramid(channel_count: usize) -> PeakPyramid {
+        let channels = (0..channel_count)
+            .map(|c| {
+                (0..10)
+                    .map(|i| Peak {
+                        min: -(((i + c) % 8) as f32) / 8.0,
+                        max: ((i * 3 + c) % 7) as f32 / 8.0,
+                    })
+                    .collect()
+            })
+            .collect();
+        PeakPyramid::from_base_bins(256, channels)
+    }
+
+    fn assert_same_peaks(read: &Audiowaveform, peaks: &PeakPyramid) {
+        assert_eq!(read.peaks.channel_count(), peaks.channel_count());
+        assert_eq!(read.peaks.base_bin_frames(), peaks.base_bin_frames());
+        for channel in 0..peaks.channel_count() {
+            assert_eq!(read.peaks.level(channel, 0), peaks.level(channel, 0));
+        }
+    }
+
+    fn options(version: u32, bits: u8) -> ExportOptions {
+        ExportOptions {
+            version,
+            bits,
+            level: 0,
+        }
+    }
+
+    #[test]
+    fn dat_round_trips() {
+        for (version, channels) in [(1, 1), (2, 1), (2, 2)] {
+            for bits in [8, 16] {
+                let peaks = pyramid(channels);
+                let mut bytes = Vec::new();
+                Audiowaveform::write_dat(&mut bytes, &peaks, 44100, &options(version, bits))
+                    .unwrap();
+                assert_eq!(
+                    i32::from_le_bytes(bytes[..4].try_into().unwrap()),
+                    version as i32
+                );
+                let read = Audiowaveform::read_dat(&bytes[..]).unwrap();
+                assert_eq!(read.sample_rate, 44100);
+                assert_eq!(read.bits, bits);
+                assert_same_peaks(&read, &peaks);
+            }
+        }
+    }
+
+    #[test]
+    fn json_round_trips() {
+        for (version, channels) in [(1, 1), (2, 1), (2, 2)] {
+            for bits in [8, 16] {
+                let peaks = pyramid(channels);
+                let mut bytes = Vec::new();
+                Audiowaveform::write_json(&mut bytes, &peaks, 48000, &options(version, bits))
+                    .unwrap();
+                let value: Value = serde_json::from_slice(&bytes).unwrap();
+                assert_eq!(value["version"], version);
+                assert_eq!(value.get("channels").is_some(), version == 2);
+                let read = Audiowaveform::read_json(&bytes[..]).unwrap();
+                assert_eq!(read.sample_rate, 48000);
+                assert_eq!(read.bits, bits);
+                assert_same_peaks(&read, &peaks);
+            }
+        }
+    }
+
+    #[test]
+    fn exports_coarser_levels_with_a_partial_pixel() {
+        let peaks = pyramid(1);
+        let mut bytes = Vec::new();
+        let options = ExportOptions {
+            level: 2,
+            ..ExportOptions::default()
+        };
+        Audiowaveform::write_dat(&mut bytes, &peaks, 44100, &options).unwrap();
+        let read = Audiowaveform::read_dat(&bytes[..]).unwrap();
+        assert_eq!(read.peaks.base_bin_frames(), 1024);
+        let bins = read.peaks.level(0, 0);
+        assert_eq!(bins.len(), 3);
+        for (pixel, bin) in bins.iter().enumerate() {
+            let start = pixel as u64 * 1024;
+            assert_eq!(*bin, peaks.summary(0, start, start + 1024));
+        }
+    }
+
+    #[test]
+    fn version_1_is_mono_only() {
+        let result = Audiowaveform::write_dat(Vec::new(), &pyramid(2), 44100, &options(1, 8));
+        assert!(matches!(result, Err(PeaksFileError::InvalidFormat(_))));
+    }
+
+    #[test]
+    fn reports_unsupported_versions_unmodified() {
+        let mut bytes = Vec::new();
+        Audiowaveform::write_dat(&mut bytes, &pyramid(1), 44100, &options(2, 8)).unwrap();
+        bytes[..4].copy_from_slice(&(-1i32).to_le_bytes());
+        assert!(matches!(
+            Audiowaveform::read_dat(&bytes[..]),
+            Err(PeaksFileError::UnsupportedVersion(-1))
+        ));
+
+        let json = br#"{"version": 65538, "sample_rate": 44100, "samples_per_pixel": 256,
+            "bits": 8, "length": 0, "data": []}"#;
+        assert!(matches!(
+            Audiowaveform::read_json(&json[..]),
+            Err(PeaksFileError::UnsupportedVersion(65538))
+        ));
+
+        let result = Audiowaveform::write_json(Vec::new(), &pyramid(1), 44100, &options(70000, 8));
+        assert!(matches!(
+            result,
+            Err(PeaksFileError::UnsupportedVersion(70000))
+        ));
+    }
+
+    #[test]
+    fn rejects_corrupt_headers_without_huge_allocation() {
+        let mut bytes = Vec::new();
+        Audiowaveform::write_dat(&mut bytes, &pyramid(2), 44100, &options(2, 8)).unwrap();
+        let mut channels = bytes.clone();
+        channels[20..24].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(matches!(
+            Audiowaveform::read_dat(&channels[..]),
+            Err(PeaksFileError::InvalidFormat(_))
+        ));
+        let mut length = bytes.clone();
+        length[16..20].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(matches!(
+            Audiowaveform::read_dat(&length[..]),
+            Err(PeaksFileError::Io(_))
+        ));
+
+        assert!(matches!(
+            Audiowaveform::write_dat(Vec::new(), &pyramid(25), 44100, &options(2, 8)),
+            Err(PeaksFileError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_json_values_out_of_range() {
+        let json = |field: &str, value: i64| {
+            let mut json = json!({
+                "sample_rate": 44100,
+                "samples_per_pixel": 256,
+                "bits": 16,
+                "data": [-1, 1],
+            });
+            json[field] = json!(value);
+            serde_json::to_vec(&json).unwrap()
+        };
+        assert!(Audiowaveform::read_json(&json("sample_rate", 48000)[..]).is_ok());
+        for (field, value) in [
+            ("sample_rate", 1 << 32),
+            ("samples_per_pixel", i64::from(i32::MAX) + 1),
+            ("channels", 1 << 32 | 1),
+            ("channels", 25),
+        ] {
+            assert!(matches!(
+                Audiowaveform::read_json(&json(field, value)[..]),
+                Err(PeaksFileError::InvalidFormat(_))
+            ));
+        }
+        let data = br#"{"sample_rate": 44100, "samples_per_pixel": 256, "bits": 16,
+            "data": [-1, 4294967297]}"#;
+        assert!(matches!(
+            Audiowaveform::read_json(&data[..]),
+            Err(PeaksFileError::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_bit_depths() {
+        let result = Audiowaveform::write_dat(Vec::new(), &pyramid(1), 44100, &options(2, 12));
+        assert!(matches!(result, Err(PeaksFileError::InvalidFormat(_))));
+
+        let json = br#"{"sample_rate": 44100, "samples_per_pixel": 256, "bits": 12, "data": []}"#;
+        assert!(matches!(
+            Audiowaveform::read_json(&json[..]),
+            Err(PeaksFileError::InvalidFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,358 @@
+use super::DecodeError;
+use crate::player::Metadata;
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{self, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+
+impl From<SymphoniaError> for DecodeError {
+    fn from(e: SymphoniaError) -> Self {
+        match e {
+            SymphoniaError::IoError(e) => DecodeError::Io(e),
+            SymphoniaError::Unsupported(what) => DecodeError::Unsupported(what.to_string()),
+            other => DecodeError::InvalidData(other.to_string()),
+        }
+    }
+}
+
+// Adapts any `Read + Seek` to symphonia's source trait
+struct Source<R> {
+    reader: R,
+    len: u64,
+}
+
+impl<R: Read> Read for Source<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl<R: Seek> Seek for Source<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.reader.seek(pos)
+    }
+}
+
+impl<R: Read + Seek + Send + Sync> MediaSource for Source<R> {
+    fn is_seekable(&self) -> bool {
+        true
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        Some(self.len)
+    }
+}
+
+pub(super) fn source_stream<R>(mut reader: R) -> Result<MediaSourceStream, DecodeError>
+where
+    R: Read + Seek + Send + Sync + 'static,
+{
+    let len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    Ok(MediaSourceStream::new(
+        Box::new(Source { reader, len }),
+        Default::default(),
+    ))
+}
+
+pub(super) fn format_options() -> FormatOptions {
+    FormatOptions {
+        // Trim encoder delay and padding so frame 0 is the first real sample
+        enable_gapless: true,
+        ..Default::default()
+    }
+}
+
+fn stream_lost() -> DecodeError {
+    DecodeError::InvalidData("stream lost by a failed seek".to_string())
+}
+
+/// Opens a format reader on a stream positioned at its start.
+pub(super) type OpenFormat = fn(MediaSourceStream) -> Result<Box<dyn FormatReader>, SymphoniaError>;
+
+/// Shared packet decoding for the compressed formats, which differ only in container.
+pub(super) struct CodecDecoder {
+    // None only after reopening the stream failed
+    format: Option<Box<dyn FormatReader>>,
+    open: OpenFormat,
+    // Set for containers whose reader must start over to seek reliably
+    reopen_on_seek: bool,
+    decoder: Box<dyn codecs::Decoder>,
+    track_id: u32,
+    metadata: Metadata,
+    encoder_delay: u32,
+    encoder_padding: u32,
+    // Decoded frames not yet handed out, one vector per channel
+    pending: Vec<Vec<f32>>,
+    pending_offset: usize,
+    // Interleaved scratch for each decoded packet, grown to the largest seen
+    samples: Option<SampleBuffer<f32>>,
+    // Decoded frames before this one are dropped, as after a seek
+    skip_to: u64,
+    position: u64,
+}
+
+impl CodecDecoder {
+    pub(super) fn new(source: MediaSourceStream, open: OpenFormat) -> Result<Self, DecodeError> {
+        let format = open(source)?;
+        let track = format
+            .default_track()
+            .ok_or_else(|| DecodeError::InvalidData("no audio track".to_string()))?;
+        let params = &track.codec_params;
+        let sample_rate = params
+            .sample_rate
+            .ok_or_else(|| DecodeError::InvalidData("unknown sample rate".to_string()))?;
+        let channel_count = params
+            .channels
+            .ok_or_else(|| DecodeError::InvalidData("unknown channel layout".to_string()))?
+            .count();
+        let metadata = Metadata {
+            sample_rate,
+            channel_count: channel_count as u32,
+            frame_count: params.n_frames.unwrap_or(0),
+        };
+        let encoder_delay = params.delay.unwrap_or(0);
+        let encoder_padding = params.padding.unwrap_or(0);
+        let track_id = track.id;
+        let decoder = symphonia::default::get_codecs().make(params, &DecoderOptions::default())?;
+
+        Ok(CodecDecoder {
+            format: Some(format),
+            open,
+            reopen_on_seek: false,
+            decoder,
+            track_id,
+            metadata,
+            encoder_delay,
+            encoder_padding,
+            pending: vec![Vec::new(); channel_count],
+            pending_offset: 0,
+            samples: None,
+            skip_to: 0,
+            position: 0,
+        })
+    }
+
+    /// Reopens the stream before every seek rather than seeking the current reader.
+    pub(super) fn reopen_on_seek(mut self) -> Self {
+        self.reopen_on_seek = true;
+        self
+    }
+
+    // Replaces the reader with a fresh one at the start of the stream
+    fn reopen(&mut self) -> Result<(), DecodeError> {
+        let mut stream = self.format.take().ok_or_else(stream_lost)?.into_inner();
+        stream.seek(SeekFrom::Start(0))?;
+        self.format = Some((self.open)(stream)?);
+        Ok(())
+    }
+
+    fn format(&mut self) -> Result<&mut Box<dyn FormatReader>, DecodeError> {
+        self.format.as_mut().ok_or_else(stream_lost)
+    }
+
+    pub(super) fn metadata(&self) -> Metadata {
+        self.metadata
+    }
+
+    pub(super) fn encoder_delay(&self) -> u32 {
+        self.encoder_delay
+    }
+
+    pub(super) fn encoder_padding(&self) -> u32 {
+        self.encoder_padding
+    }
+
+    pub(super) fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub(super) fn read(
+        &mut self,
+        buffers: &mut [Vec<f32>],
+        max_frames: usize,
+    ) -> Result<usize, DecodeError> {
+        if buffers.len() != self.pending.len() {
+            return Err(DecodeError::InvalidData(format!(
+                "expected {} channel buffers, got {}",
+                self.pending.len(),
+                buffers.len()
+            )));
+        }
+        for buffer in buffers.iter_mut() {
+            buffer.clear();
+        }
+
+        let mut frames = 0;
+        while frames < max_frames {
+            let available = self.pending[0].len() - self.pending_offset;
+            if available == 0 {
+                if self.decode_packet()?.is_none() {
+                    break;
+                }
+                continue;
+            }
+            let take = available.min(max_frames - frames);
+            for (buffer, pending) in buffers.iter_mut().zip(&self.pending) {
+                buffer.extend_from_slice(&pending[self.pending_offset..self.pending_offset + take]);
+            }
+            self.pending_offset += take;
+            frames += take;
+        }
+        self.position += frames as u64;
+        Ok(frames)
+    }
+
+    // Decodes the next packet of our track into `pending`, less any frames
+    // before `skip_to`, and returns the frames it decoded to; None at end of stream.
+    fn decode_packet(&mut self) -> Result<Option<Range<u64>>, DecodeError> {
+        loop {
+            let packet = match self.format()?.next_packet() {
+                Ok(packet) => packet,
+                Err(SymphoniaError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Ok(None);
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // A corrupt packet is dropped rather than ending the stream
+                Err(SymphoniaError::DecodeError(_)) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            let channel_count = decoded.spec().channels.count();
+            let frames = decoded.frames() as u64;
+            if channel_count != self.pending.len() {
+                return Err(DecodeError::InvalidData(format!(
+                    "channel count changed from {} to {}",
+                    self.pending.len(),
+                    channel_count
+                )));
+            }
+            if self
+                .samples
+                .as_ref()
+                .is_none_or(|samples| samples.capacity() < decoded.capacity() * channel_count)
+            {
+                self.samples = None;
+            }
+            let samples = self.samples.get_or_insert_with(|| {
+                SampleBuffer::new(decoded.capacity() as u64, *decoded.spec())
+            });
+            samples.copy_interleaved_ref(decoded);
+
+            // The output ends where the packet does; a decoder can hold back the
+            // start, as Vorbis does with the whole first packet after a reset
+            let end = packet.ts() + packet.dur();
+            let start = end.saturating_sub(frames);
+            let skip = self.skip_to.saturating_sub(start).min(frames) as usize;
+            for pending in self.pending.iter_mut() {
+                pending.clear();
+            }
+            for frame in samples.samples().chunks_exact(channel_count).skip(skip) {
+                for (pending, &sample) in self.pending.iter_mut().zip(frame) {
+                    pending.push(sample);
+                }
+            }
+            self.pending_offset = 0;
+            return Ok(Some(start..end));
+        }
+    }
+
+    pub(super) fn seek(&mut self, frame: u64) -> Result<(), DecodeError> {
+        if self.reopen_on_seek {
+            // symphonia's FLAC reader keeps the last frame header it parsed across a
+            // seek that lands on a frame boundary, then skips every earlier frame as
+            // out of sequence; a fresh reader has no such history
+            self.reopen()?;
+        }
+        self.skip_to = frame;
+        self.position = frame;
+
+        // Decoders can need a packet of priming before they output anything, so
+        // if the audio after the seek starts past `frame`, or never comes, seek a
+        // packet earlier
+        let track_id = self.track_id;
+        let mut target = frame;
+        loop {
+            let seeked = self.format()?.seek(
+                SeekMode::Accurate,
+                SeekTo::TimeStamp {
+                    ts: target,
+                    track_id,
+                },
+            )?;
+            self.decoder.reset();
+            let decoded = loop {
+                match self.decode_packet()? {
+                    Some(decoded) if decoded.is_empty() => continue,
+                    decoded => break decoded,
+                }
+            };
+            if decoded.is_some_and(|decoded| decoded.start <= frame) {
+                return Ok(());
+            }
+            if seeked.actual_ts == 0 {
+                // Nothing earlier to seek to, so decode from the very start
+                self.reopen()?;
+                self.decoder.reset();
+                for pending in self.pending.iter_mut() {
+                    pending.clear();
+                }
+                self.pending_offset = 0;
+                return Ok(());
+            }
+            target = seeked.actual_ts - 1;
+        }
+    }
+}
+
+// Implements `Decoder` for a wrapper struct holding a `CodecDecoder` in `inner`
+macro_rules! delegate_decoder {
+    ($name:ident) => {
+        impl<R: Read + Seek + Send + Sync + 'static> super::Decoder<R> for $name<R> {
+            fn open(reader: R) -> Result<Self, DecodeError> {
+                $name::open(reader)
+            }
+
+            fn metadata(&self) -> Metadata {
+                self.inner.metadata()
+            }
+
+            fn position(&self) -> u64 {
+                self.inner.position()
+            }
+
+            fn read(
+                &mut self,
+                buffers: &mut [Vec<f32>],
+                max_frames: usize,
+            ) -> Result<usize, DecodeError> {
+                self.inner.read(buffers, max_frames)
+            }
+
+            fn seek(&mut self, frame: u64) -> Result<(), DecodeError> {
+                self.inner.seek(frame)
+            }
+
+            fn encoder_delay(&self) -> u32 {
+                self.inner.encoder_delay()
+            }
+
+            fn encoder_padding(&self) -> u32 {
+                self.inner.encoder_padding()
+            }
+        }
+    };
+}
+
+pub(super) use delegate_decoder;
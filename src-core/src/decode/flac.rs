@@ -0,0 +1,149 @@
+use super::DecodeError;
+use super::codec::{CodecDecoder, delegate_decoder, format_options, source_stream};
+use crate::player::Metadata;
+
+use std::io::{Read, Seek};
+use std::marker::PhantomData;
+
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatReader;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::default::formats::FlacReader;
+
+/// FLAC decoder.
+pub struct FlacDecoder<R> {
+    inner: CodecDecoder,
+    _reader: PhantomData<fn() -> R>,
+}
+
+impl<R: Read + Seek + Send + Sync + 'static> FlacDecoder<R> {
+    pub fn open(reader: R) -> Result<Self, DecodeError> {
+        Ok(FlacDecoder {
+            inner: CodecDecoder::new(source_stream(reader)?, open_format)?.reopen_on_seek(),
+            _reader: PhantomData,
+        })
+    }
+}
+
+fn open_format(source: MediaSourceStream) -> Result<Box<dyn FormatReader>, SymphoniaError> {
+    Ok(Box::new(FlacReader::try_new(source, &format_options())?))
+}
+
+delegate_decoder!(FlacDecoder);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::Decoder;
+    use std::io::Cursor;
+
+    const BLOCK: usize = 256;
+
+    // A distinct, deterministic 16-bit sample for every frame and channel
+    fn sample(channel: usize, frame: usize) -> i16 {
+        (((frame * 97 + channel * 5003) % 60000) as i32 - 30000) as i16
+    }
+
+    fn crc8(bytes: &[u8]) -> u8 {
+        bytes.iter().fold(0, |crc, &byte| {
+            (0..8).fold(crc ^ byte, |crc, _| {
+                if crc & 0x80 != 0 {
+                    crc << 1 ^ 0x07
+                } else {
+                    crc << 1
+                }
+            })
+        })
+    }
+
+    fn crc16(bytes: &[u8]) -> u16 {
+        bytes.iter().fold(0, |crc, &byte| {
+            (0..8).fold(crc ^ (byte as u16) << 8, |crc, _| {
+                if crc & 0x8000 != 0 {
+                    crc << 1 ^ 0x8005
+                } else {
+                    crc << 1
+                }
+            })
+        })
+    }
+
+    // 16-bit FLAC at 44.1 kHz in fixed blocks of verbatim subframes
+    fn flac(channels: usize, frames: usize) -> Vec<u8> {
+        let mut bytes = b"fLaC".to_vec();
+        bytes.extend([0x80, 0, 0, 34]); // last metadata block, STREAMINFO
+        bytes.extend((BLOCK as u16).to_be_bytes());
+        bytes.extend((BLOCK as u16).to_be_bytes());
+        bytes.extend([0; 6]); // frame sizes unknown
+        let packed = 44100u64 << 44 | (channels as u64 - 1) << 41 | 15 << 36 | frames as u64;
+        bytes.extend(packed.to_be_bytes());
+        bytes.extend([0; 16]); // no MD5
+
+        for (index, start) in (0..frames).step_by(BLOCK).enumerate() {
+            let len = BLOCK.min(frames - start);
+            assert!(index < 128, "frame numbers past one UTF-8 byte");
+            let mut frame = vec![0xFF, 0xF8, 0x70, (channels as u8 - 1) << 4 | 0b100 << 1];
+            frame.push(index as u8);
+            frame.extend((len as u16 - 1).to_be_bytes());
+            frame.push(crc8(&frame));
+            for channel in 0..channels {
+                frame.push(0x02); // verbatim subframe
+                for i in start..start + len {
+                    frame.extend(sample(channel, i).to_be_bytes());
+                }
+            }
+            frame.extend(crc16(&frame).to_be_bytes());
+            bytes.extend(frame);
+        }
+        bytes
+    }
+
+    fn open(bytes: Vec<u8>) -> FlacDecoder<Cursor<Vec<u8>>> {
+        FlacDecoder::open(Cursor::new(bytes)).unwrap()
+    }
+
+    // Reads `frames` frames, or to the end, checking each sample against the source
+    fn check_read(decoder: &mut FlacDecoder<Cursor<Vec<u8>>>, frames: usize) -> usize {
+        let channels = decoder.metadata().channel_count as usize;
+        let mut buffers = vec![Vec::new(); channels];
+        let mut read = 0;
+        while read < frames {
+            let start = decoder.position() as usize;
+            let count = decoder.read(&mut buffers, 100.min(frames - read)).unwrap();
+            if count == 0 {
+                break;
+            }
+            for (channel, buffer) in buffers.iter().enumerate() {
+                for (i, &value) in buffer.iter().enumerate() {
+                    let expected = sample(channel, start + i) as f32 / 32768.0;
+                    assert_eq!(value, expected, "channel {} frame {}", channel, start + i);
+                }
+            }
+            read += count;
+        }
+        read
+    }
+
+    #[test]
+    fn decodes_every_sample() {
+        let frames = 10 * BLOCK + 57;
+        let mut decoder = open(flac(2, frames));
+        let metadata = decoder.metadata();
+        assert_eq!(metadata.sample_rate, 44100);
+        assert_eq!(metadata.channel_count, 2);
+        assert_eq!(metadata.frame_count, frames as u64);
+        assert_eq!(check_read(&mut decoder, usize::MAX), frames);
+        assert_eq!(decoder.position(), frames as u64);
+    }
+
+    #[test]
+    fn seeks_to_the_exact_frame() {
+        let frames = 10 * BLOCK + 57;
+        let mut decoder = open(flac(2, frames));
+        for frame in [5 * BLOCK + 13, 1, BLOCK, 9 * BLOCK - 1, frames - 3, 0] {
+            decoder.seek(frame as u64).unwrap();
+            assert_eq!(decoder.position(), frame as u64);
+            assert_eq!(check_read(&mut decoder, 300), 300.min(frames - frame));
+        }
+    }
+}
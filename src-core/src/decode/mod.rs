@@ -0,0 +1,114 @@
+#[cfg(any(feature = "flac", feature = "mp3", feature = "vorbis"))]
+mod codec;
+#[cfg(feature = "flac")]
+pub mod flac;
+#[cfg(feature = "mp3")]
+pub mod mp3;
+#[cfg(feature = "vorbis")]
+pub mod vorbis;
+pub mod wav;
+
+#[cfg(feature = "flac")]
+pub use flac::FlacDecoder;
+#[cfg(feature = "mp3")]
+pub use mp3::Mp3Decoder;
+#[cfg(feature = "vorbis")]
+pub use vorbis::VorbisDecoder;
+pub use wav::WavReader;
+
+use crate::player::Metadata;
+
+use std::fmt;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Source of decoded audio, read as deinterleaved `f32` frames.
+///
+/// Frame indices count audio as the listener hears it: encoder priming and
+/// padding are already trimmed, so frame `n` here is frame `n` in playback.
+pub trait Decoder<R: Read + Seek> {
+    fn open(reader: R) -> Result<Self, DecodeError>
+    where
+        Self: Sized;
+
+    /// Format of the stream. Its `frame_count` is 0 when the container does
+    /// not record the length; reading to the end is then the only way to find it.
+    fn metadata(&self) -> Metadata;
+
+    /// Frame the next `read` starts at.
+    fn position(&self) -> u64;
+
+    /// Replaces the contents of `buffers` (one per channel) with up to
+    /// `max_frames` frames and returns how many were read; 0 at end of stream.
+    fn read(&mut self, buffers: &mut [Vec<f32>], max_frames: usize) -> Result<usize, DecodeError>;
+
+    fn seek(&mut self, frame: u64) -> Result<(), DecodeError>;
+
+    /// Priming frames the encoder added before the audio, trimmed from the output.
+    fn encoder_delay(&self) -> u32 {
+        0
+    }
+
+    /// Frames the encoder added after the audio, trimmed from the output.
+    fn encoder_padding(&self) -> u32 {
+        0
+    }
+}
+
+/// Opens `reader` with whichever enabled decoder recognizes its header.
+pub fn open<R>(mut reader: R) -> Result<Box<dyn Decoder<R> + Send>, DecodeError>
+where
+    R: Read + Seek + Send + Sync + 'static,
+{
+    let mut magic = [0u8; 4];
+    let read = reader.read(&mut magic)?;
+    reader.seek(SeekFrom::Start(0))?;
+    let magic = &magic[..read];
+
+    match magic {
+        b"RIFF" | b"RF64" | b"BW64" => Ok(Box::new(WavReader::new(reader)?)),
+        #[cfg(feature = "flac")]
+        b"fLaC" => Ok(Box::new(FlacDecoder::open(reader)?)),
+        #[cfg(feature = "vorbis")]
+        b"OggS" => Ok(Box::new(VorbisDecoder::open(reader)?)),
+        // ID3v2 tag or an MPEG audio frame sync
+        #[cfg(feature = "mp3")]
+        [b'I', b'D', b'3', ..] | [0xFF, 0xE0..=0xFF, ..] => Ok(Box::new(Mp3Decoder::open(reader)?)),
+        _ => Err(DecodeError::Unsupported(
+            "unrecognized or disabled audio format".to_string(),
+        )),
+    }
+}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Io(io::Error),
+    /// The stream is malformed or truncated.
+    InvalidData(String),
+    /// The stream is well-formed but uses an encoding we do not decode.
+    Unsupported(String),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Io(e) => write!(f, "I/O error: {}", e),
+            DecodeError::InvalidData(message) => write!(f, "Invalid data: {}", message),
+            DecodeError::Unsupported(message) => write!(f, "Unsupported format: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(e: io::Error) -> Self {
+        DecodeError::Io(e)
+    }
+}
@@ -0,0 +1,111 @@
+use super::DecodeError;
+use super::codec::{CodecDecoder, delegate_decoder, format_options, source_stream};
+use crate::player::Metadata;
+
+use std::io::{Read, Seek};
+use std::marker::PhantomData;
+
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatReader;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::default::formats::MpaReader;
+
+/// MP3 decoder.
+pub struct Mp3Decoder<R> {
+    inner: CodecDecoder,
+    _reader: PhantomData<fn() -> R>,
+}
+
+impl<R: Read + Seek + Send + Sync + 'static> Mp3Decoder<R> {
+    pub fn open(reader: R) -> Result<Self, DecodeError> {
+        Ok(Mp3Decoder {
+            inner: CodecDecoder::new(source_stream(reader)?, open_format)?,
+            _reader: PhantomData,
+        })
+    }
+}
+
+fn open_format(source: MediaSourceStream) -> Result<Box<dyn FormatReader>, SymphoniaError> {
+    Ok(Box::new(MpaReader::try_new(source, &format_options())?))
+}
+
+delegate_decoder!(Mp3Decoder);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::Decoder;
+    use std::io::Cursor;
+
+    // MPEG-1 Layer III, 128 kbps, 48 kHz mono: 384 bytes and 1152 frames each
+    const HEADER: [u8; 4] = [0xFF, 0xFB, 0x94, 0xC0];
+    const FRAME_BYTES: usize = 384;
+    const SIDE_INFO_BYTES: usize = 17;
+
+    // `frames` silent frames after an Info tag whose LAME extension records
+    // `delay` and `padding` as an encoder would
+    fn mp3(frames: u32, delay: u32, padding: u32) -> Vec<u8> {
+        let mut info = HEADER.to_vec();
+        info.extend([0; SIDE_INFO_BYTES]);
+        info.extend(b"Info");
+        info.extend(1u32.to_be_bytes()); // frame count present
+        info.extend(frames.to_be_bytes());
+        info.extend(b"Lavf58.76");
+        info.extend([0; 12]); // revision through ABR
+        let trim = (delay - 529) << 12 | (padding + 529);
+        info.extend(&trim.to_be_bytes()[1..]);
+        info.resize(FRAME_BYTES, 0);
+
+        let mut bytes = info;
+        for _ in 0..frames {
+            bytes.extend(HEADER);
+            bytes.resize(bytes.len() + FRAME_BYTES - HEADER.len(), 0);
+        }
+        bytes
+    }
+
+    fn open(bytes: Vec<u8>) -> Mp3Decoder<Cursor<Vec<u8>>> {
+        Mp3Decoder::open(Cursor::new(bytes)).unwrap()
+    }
+
+    fn read_to_end(decoder: &mut Mp3Decoder<Cursor<Vec<u8>>>) -> u64 {
+        let mut buffers = vec![Vec::new()];
+        let mut frames = 0;
+        loop {
+            let read = decoder.read(&mut buffers, 1000).unwrap();
+            if read == 0 {
+                return frames;
+            }
+            assert!(buffers[0].iter().all(|&sample| sample == 0.0));
+            frames += read as u64;
+        }
+    }
+
+    #[test]
+    fn trims_encoder_delay_and_padding() {
+        let mut decoder = open(mp3(10, 1105, 471));
+        assert_eq!(decoder.encoder_delay(), 1105);
+        assert_eq!(decoder.encoder_padding(), 471);
+        let metadata = decoder.metadata();
+        assert_eq!(metadata.sample_rate, 48000);
+        assert_eq!(metadata.channel_count, 1);
+        assert_eq!(metadata.frame_count, 10 * 1152 - 1105 - 471);
+        assert_eq!(read_to_end(&mut decoder), metadata.frame_count);
+    }
+
+    #[test]
+    fn seeks_to_the_exact_frame() {
+        let mut decoder = open(mp3(10, 1105, 471));
+        let frame_count = decoder.metadata().frame_count;
+        for frame in [0, 1, 1151, 5000, frame_count - 1] {
+            decoder.seek(frame).unwrap();
+            assert_eq!(decoder.position(), frame);
+            assert_eq!(
+                read_to_end(&mut decoder),
+                frame_count - frame,
+                "from {}",
+                frame
+            );
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use super::DecodeError;
+use super::codec::{CodecDecoder, delegate_decoder, format_options, source_stream};
+use crate::player::Metadata;
+
+use std::io::{Read, Seek};
+use std::marker::PhantomData;
+
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatReader;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::default::formats::OggReader;
+
+/// Ogg Vorbis decoder.
+pub struct VorbisDecoder<R> {
+    inner: CodecDecoder,
+    _reader: PhantomData<fn() -> R>,
+}
+
+impl<R: Read + Seek + Send + Sync + 'static> VorbisDecoder<R> {
+    pub fn open(reader: R) -> Result<Self, DecodeError> {
+        Ok(VorbisDecoder {
+            inner: CodecDecoder::new(source_stream(reader)?, open_format)?,
+            _reader: PhantomData,
+        })
+    }
+}
+
+fn open_format(source: MediaSourceStream) -> Result<Box<dyn FormatReader>, SymphoniaError> {
+    Ok(Box::new(OggReader::try_new(source, &format_options())?))
+}
+
+delegate_decoder!(VorbisDecoder);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::Decoder;
+    use std::io::Cursor;
+
+    // Both block sizes are 256, so every audio packet after the first adds 128 frames
+    const PACKET_FRAMES: u64 = 128;
+    const PACKETS_PER_PAGE: u64 = 10;
+
+    // Packs values least significant bit first, as Vorbis does
+    #[derive(Default)]
+    struct Bits {
+        bytes: Vec<u8>,
+        used: u32,
+    }
+
+    impl Bits {
+        fn put(&mut self, value: u32, bits: u32) {
+            for i in 0..bits {
+                if self.used.is_multiple_of(8) {
+                    self.bytes.push(0);
+                }
+                let bit = (value >> i & 1) as u8;
+                *self.bytes.last_mut().unwrap() |= bit << (self.used % 8);
+                self.used += 1;
+            }
+        }
+    }
+
+    fn crc32(bytes: &[u8]) -> u32 {
+        bytes.iter().fold(0, |crc, &byte| {
+            (0..8).fold(crc ^ (byte as u32) << 24, |crc, _| {
+                if crc & 0x8000_0000 != 0 {
+                    crc << 1 ^ 0x04C1_1DB7
+                } else {
+                    crc << 1
+                }
+            })
+        })
+    }
+
+    fn page(packets: &[Vec<u8>], granule: u64, sequence: u32, flags: u8) -> Vec<u8> {
+        let mut lacing = Vec::new();
+        for packet in packets {
+            lacing.extend(vec![255; packet.len() / 255]);
+            lacing.push((packet.len() % 255) as u8);
+        }
+        let mut bytes = b"OggS".to_vec();
+        bytes.extend([0, flags]);
+        bytes.extend(granule.to_le_bytes());
+        bytes.extend(1u32.to_le_bytes()); // serial
+        bytes.extend(sequence.to_le_bytes());
+        bytes.extend([0; 4]); // CRC, filled in below
+        bytes.push(lacing.len() as u8);
+        bytes.extend(lacing);
+        for packet in packets {
+            bytes.extend(packet);
+        }
+        let crc = crc32(&bytes);
+        bytes[22..26].copy_from_slice(&crc.to_le_bytes());
+        bytes
+    }
+
+    fn header(packet_type: u8) -> Vec<u8> {
+        let mut bytes = vec![packet_type];
+        bytes.extend(b"vorbis");
+        bytes
+    }
+
+    // A stream of `frames` silent frames at 44.1 kHz: one mode, one floor 1
+    // with no partitions, and audio packets that each mark every floor unused
+    fn ogg(channels: u8, frames: u64) -> Vec<u8> {
+        let mut identification = header(1);
+        identification.extend(0u32.to_le_bytes());
+        identification.push(channels);
+        identification.extend(44100u32.to_le_bytes());
+        identification.extend([0; 12]); // bitrates
+        identification.extend([0x88, 1]); // block sizes 2^8, framing
+
+        let mut comment = header(3);
+        comment.extend(9u32.to_le_bytes());
+        comment.extend(b"driftwave");
+        comment.extend(0u32.to_le_bytes());
+        comment.push(1);
+
+        let mut bits = Bits::default();
+        bits.put(0, 8); // one codebook
+        bits.put(0x564342, 24);
+        bits.put(1, 16); // dimensions
+        bits.put(2, 24); // entries
+        bits.put(0, 2); // unordered, dense
+        bits.put(0, 10); // two one-bit codewords
+        bits.put(0, 4); // no lookup
+        bits.put(0, 6 + 16); // one time domain transform
+        bits.put(0, 6);
+        bits.put(1, 16); // floor 1
+        bits.put(0, 5 + 2); // no partitions, multiplier 1
+        bits.put(7, 4); // range bits
+        bits.put(0, 6);
+        bits.put(0, 16); // residue 0
+        bits.put(0, 24); // begin
+        bits.put(0, 24); // end
+        bits.put(0, 24); // partition size
+        bits.put(0, 6 + 8 + 3 + 1); // one classification, no books
+        bits.put(0, 6);
+        bits.put(0, 16); // mapping 0
+        bits.put(0, 1 + 1 + 2); // one submap, no coupling
+        bits.put(0, 8 + 8 + 8); // floor 0, residue 0
+        bits.put(0, 6);
+        bits.put(0, 1); // short blocks
+        bits.put(0, 16 + 16); // window and transform
+        bits.put(0, 8); // mapping 0
+        bits.put(1, 1); // framing
+        let mut setup = header(5);
+        setup.extend(bits.bytes);
+
+        let mut bytes = page(&[identification], 0, 0, 0x02);
+        bytes.extend(page(&[comment, setup], 0, 1, 0));
+        let packets = frames.div_ceil(PACKET_FRAMES) + 1;
+        let firsts = (0..packets).step_by(PACKETS_PER_PAGE as usize);
+        for (sequence, first) in (2..).zip(firsts) {
+            let count = PACKETS_PER_PAGE.min(packets - first);
+            let last = first + count == packets;
+            let granule = if last {
+                frames
+            } else {
+                (first + count - 1) * PACKET_FRAMES
+            };
+            let audio = vec![vec![0u8]; count as usize];
+            bytes.extend(page(&audio, granule, sequence, if last { 0x04 } else { 0 }));
+        }
+        bytes
+    }
+
+    fn open(bytes: Vec<u8>) -> VorbisDecoder<Cursor<Vec<u8>>> {
+        VorbisDecoder::open(Cursor::new(bytes)).unwrap()
+    }
+
+    fn read_to_end(decoder: &mut VorbisDecoder<Cursor<Vec<u8>>>) -> u64 {
+        let mut buffers = vec![Vec::new(); decoder.metadata().channel_count as usize];
+        let mut frames = 0;
+        loop {
+            let read = decoder.read(&mut buffers, 1000).unwrap();
+            if read == 0 {
+                return frames;
+            }
+            assert!(buffers.iter().flatten().all(|&sample| sample == 0.0));
+            frames += read as u64;
+        }
+    }
+
+    #[test]
+    fn trims_to_the_final_granule_position() {
+        let mut decoder = open(ogg(2, 5000));
+        let metadata = decoder.metadata();
+        assert_eq!(metadata.sample_rate, 44100);
+        assert_eq!(metadata.channel_count, 2);
+        assert_eq!(metadata.frame_count, 5000);
+        assert_eq!(read_to_end(&mut decoder), 5000);
+    }
+
+    #[test]
+    fn seeks_to_the_exact_frame() {
+        let mut decoder = open(ogg(2, 5000));
+        for frame in [2500, 1, 127, 1280, 4999, 0] {
+            decoder.seek(frame).unwrap();
+            assert_eq!(decoder.position(), frame);
+            assert_eq!(read_to_end(&mut decoder), 5000 - frame, "from {}", frame);
+        }
+    }
+}
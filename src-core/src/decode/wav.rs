@@ -0,0 +1,628 @@
+use super::{DecodeError, Decoder};
+use crate::player::Metadata;
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const WAVE_FORMAT_PCM: u16 = 0x0001;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 0x0003;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+
+// RF64 stores 0xFFFFFFFF in 32-bit size fields and the real size in ds64
+const RF64_SIZE_PLACEHOLDER: u32 = 0xFFFF_FFFF;
+
+// Bytes of a fmt or ds64 chunk that are parsed; anything past them is skipped
+// unread, so a corrupt size cannot force a huge allocation
+const HEADER_CHUNK_BYTES: u32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    U8,
+    I16,
+    I24,
+    I32,
+    F32,
+    F64,
+}
+
+impl SampleFormat {
+    fn bytes(&self) -> usize {
+        match self {
+            SampleFormat::U8 => 1,
+            SampleFormat::I16 => 2,
+            SampleFormat::I24 => 3,
+            SampleFormat::I32 | SampleFormat::F32 => 4,
+            SampleFormat::F64 => 8,
+        }
+    }
+
+    fn decode(&self, bytes: &[u8]) -> f32 {
+        match self {
+            SampleFormat::U8 => (bytes[0] as f32 - 128.0) / 128.0,
+            SampleFormat::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f32 / 32768.0,
+            SampleFormat::I24 => {
+                // Shift into the top of an i32 to sign-extend
+                let value = i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8;
+                value as f32 / 8_388_608.0
+            }
+            SampleFormat::I32 => {
+                i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32
+                    / 2_147_483_648.0
+            }
+            SampleFormat::F32 => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            SampleFormat::F64 => f64::from_le_bytes([
+                bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+            ]) as f32,
+        }
+    }
+}
+
+/// Reader for RIFF/WAVE files, including RF64/BW64 and Broadcast WAVE.
+///
+/// Decodes integer PCM (8/16/24/32-bit) and IEEE float (32/64-bit), plain or
+/// WAVEFORMATEXTENSIBLE, into deinterleaved `f32` samples in `-1.0..1.0`.
+pub struct WavReader<R> {
+    reader: R,
+    format: SampleFormat,
+    sample_rate: u32,
+    channel_count: u16,
+    bits_per_sample: u16,
+    block_align: usize,
+    channel_mask: Option<u32>,
+    data_offset: u64,
+    frame_count: u64,
+    position: u64,
+    scratch: Vec<u8>,
+}
+
+impl WavReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, DecodeError> {
+        WavReader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> WavReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, DecodeError> {
+        let stream_len = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        let mut riff = [0u8; 12];
+        reader.read_exact(&mut riff)?;
+        let is_rf64 = match &riff[0..4] {
+            b"RIFF" => false,
+            b"RF64" | b"BW64" => true,
+            _ => return Err(DecodeError::InvalidData("missing RIFF header".to_string())),
+        };
+        if &riff[8..12] != b"WAVE" {
+            return Err(DecodeError::InvalidData("not a WAVE file".to_string()));
+        }
+
+        let mut ds64_data_size: Option<u64> = None;
+        let mut fmt: Option<Fmt> = None;
+        let mut offset = 12u64;
+
+        loop {
+            let mut header = [0u8; 8];
+            if offset + 8 > stream_len {
+                return Err(DecodeError::InvalidData("no data chunk".to_string()));
+            }
+            reader.read_exact(&mut header)?;
+            offset += 8;
+            let id = [header[0], header[1], header[2], header[3]];
+            let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+
+            match &id {
+                b"ds64" if is_rf64 => {
+                    let body = read_head(&mut reader, size)?;
+                    if body.len() < 24 {
+                        return Err(DecodeError::InvalidData("ds64 chunk too short".to_string()));
+                    }
+                    ds64_data_size = Some(read_u64(&body, 8));
+                }
+                b"fmt " => {
+                    let body = read_head(&mut reader, size)?;
+                    fmt = Some(Fmt::parse(&body)?);
+                }
+                b"data" => {
+                    let fmt = fmt.ok_or_else(|| {
+                        DecodeError::InvalidData("data chunk before fmt chunk".to_string())
+                    })?;
+                    let declared = match ds64_data_size {
+                        Some(size64) if size == RF64_SIZE_PLACEHOLDER => size64,
+                        _ => size as u64,
+                    };
+                    // Files still being written often carry a zero or placeholder size
+                    let available = stream_len - offset;
+                    let data_size = if declared == 0 || declared > available {
+                        available
+                    } else {
+                        declared
+                    };
+                    let block_align = fmt.block_align as usize;
+                    return Ok(WavReader {
+                        reader,
+                        format: fmt.format,
+                        sample_rate: fmt.sample_rate,
+                        channel_count: fmt.channel_count,
+                        bits_per_sample: fmt.bits_per_sample,
+                        block_align,
+                        channel_mask: fmt.channel_mask,
+                        data_offset: offset,
+                        frame_count: data_size / block_align as u64,
+                        position: 0,
+                        scratch: Vec::new(),
+                    });
+                }
+                _ => {
+                    reader.seek(SeekFrom::Current(size as i64))?;
+                }
+            }
+            offset += size as u64;
+
+            // Chunks are word-aligned
+            if size % 2 == 1 {
+                reader.seek(SeekFrom::Current(1))?;
+                offset += 1;
+            }
+        }
+    }
+
+    pub fn metadata(&self) -> Metadata {
+        Metadata {
+            sample_rate: self.sample_rate,
+            channel_count: self.channel_count as u32,
+            frame_count: self.frame_count,
+        }
+    }
+
+    pub fn sample_format(&self) -> SampleFormat {
+        self.format
+    }
+
+    /// Valid bits per sample; may be fewer than the container size for extensible files.
+    pub fn bits_per_sample(&self) -> u16 {
+        self.bits_per_sample
+    }
+
+    /// Speaker positions from WAVEFORMATEXTENSIBLE (`SPEAKER_*` bits), if present.
+    pub fn channel_mask(&self) -> Option<u32> {
+        self.channel_mask
+    }
+
+    /// Frame the next `read` starts at.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Replaces the contents of `buffers` (one per channel) with up to
+    /// `max_frames` frames and returns how many were read; 0 at end of stream.
+    pub fn read(
+        &mut self,
+        buffers: &mut [Vec<f32>],
+        max_frames: usize,
+    ) -> Result<usize, DecodeError> {
+        if buffers.len() != self.channel_count as usize {
+            return Err(DecodeError::InvalidData(format!(
+                "expected {} channel buffers, got {}",
+                self.channel_count,
+                buffers.len()
+            )));
+        }
+        let remaining = self.frame_count - self.position;
+        let frames = (max_frames as u64).min(remaining) as usize;
+        for buffer in buffers.iter_mut() {
+            buffer.clear();
+        }
+        if frames == 0 {
+            return Ok(0);
+        }
+
+        self.scratch.resize(frames * self.block_align, 0);
+        self.reader.read_exact(&mut self.scratch)?;
+
+        let sample_bytes = self.format.bytes();
+        for frame in self.scratch.chunks_exact(self.block_align) {
+            for (channel, buffer) in buffers.iter_mut().enumerate() {
+                let at = channel * sample_bytes;
+                buffer.push(self.format.decode(&frame[at..at + sample_bytes]));
+            }
+        }
+        self.position += frames as u64;
+        Ok(frames)
+    }
+
+    pub fn seek(&mut self, frame: u64) -> Result<(), DecodeError> {
+        let frame = frame.min(self.frame_count);
+        self.reader.seek(SeekFrom::Start(
+            self.data_offset + frame * self.block_align as u64,
+        ))?;
+        self.position = frame;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Fmt {
+    format: SampleFormat,
+    sample_rate: u32,
+    channel_count: u16,
+    block_align: u16,
+    bits_per_sample: u16,
+    channel_mask: Option<u32>,
+}
+
+impl Fmt {
+    fn parse(body: &[u8]) -> Result<Self, DecodeError> {
+        if body.len() < 16 {
+            return Err(DecodeError::InvalidData("fmt chunk too short".to_string()));
+        }
+        let mut tag = read_u16(body, 0);
+        let channel_count = read_u16(body, 2);
+        let sample_rate = read_u32(body, 4);
+        let block_align = read_u16(body, 12);
+        let container_bits = read_u16(body, 14);
+        let mut bits_per_sample = container_bits;
+        let mut channel_mask = None;
+
+        if tag == WAVE_FORMAT_EXTENSIBLE {
+            if body.len() < 40 {
+                return Err(DecodeError::InvalidData(
+                    "extensible fmt chunk too short".to_string(),
+                ));
+            }
+            let valid_bits = read_u16(body, 18);
+            if valid_bits != 0 {
+                bits_per_sample = valid_bits;
+            }
+            channel_mask = Some(read_u32(body, 20));
+            // Sub-format GUIDs share a fixed suffix; the first two bytes carry the format tag
+            tag = read_u16(body, 24);
+        }
+
+        let format = match (tag, container_bits) {
+            (WAVE_FORMAT_PCM, 8) => SampleFormat::U8,
+            (WAVE_FORMAT_PCM, 16) => SampleFormat::I16,
+            (WAVE_FORMAT_PCM, 24) => SampleFormat::I24,
+            (WAVE_FORMAT_PCM, 32) => SampleFormat::I32,
+            (WAVE_FORMAT_IEEE_FLOAT, 32) => SampleFormat::F32,
+            (WAVE_FORMAT_IEEE_FLOAT, 64) => SampleFormat::F64,
+            _ => {
+                return Err(DecodeError::Unsupported(format!(
+                    "format tag {:#06x} with {} bits per sample",
+                    tag, container_bits
+                )));
+            }
+        };
+
+        if channel_count == 0 || sample_rate == 0 {
+            return Err(DecodeError::InvalidData(
+                "zero channels or sample rate".to_string(),
+            ));
+        }
+        if (block_align as usize) < channel_count as usize * format.bytes() {
+            return Err(DecodeError::InvalidData(format!(
+                "block align {} too small for {} channels",
+                block_align, channel_count
+            )));
+        }
+
+        Ok(Fmt {
+            format,
+            sample_rate,
+            channel_count,
+            block_align,
+            bits_per_sample,
+            channel_mask,
+        })
+    }
+}
+
+// Reads up to `HEADER_CHUNK_BYTES` of a `size`-byte chunk and seeks past the rest
+fn read_head<R: Read + Seek>(reader: &mut R, size: u32) -> Result<Vec<u8>, DecodeError> {
+    let mut body = vec![0u8; size.min(HEADER_CHUNK_BYTES) as usize];
+    reader.read_exact(&mut body)?;
+    reader.seek(SeekFrom::Current((size - body.len() as u32) as i64))?;
+    Ok(body)
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&bytes[at..at + 8]);
+    u64::from_le_bytes(value)
+}
+
+impl<R: Read + Seek> Decoder<R> for WavReader<R> {
+    fn open(reader: R) -> Result<Self, DecodeError> {
+        WavReader::new(reader)
+    }
+
+    fn metadata(&self) -> Metadata {
+        WavReader::metadata(self)
+    }
+
+    fn position(&self) -> u64 {
+        WavReader::position(self)
+    }
+
+    fn read(&mut self, buffers: &mut [Vec<f32>], max_frames: usize) -> Result<usize, DecodeError> {
+        WavReader::read(self, buffers, max_frames)
+    }
+
+    fn seek(&mut self, frame: u64) -> Result<(), DecodeError> {
+        WavReader::seek(self, frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend((body.len() as u32).to_le_bytes());
+        bytes.extend(body);
+        if body.len() % 2 == 1 {
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    fn riff(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = chunks.concat();
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend((4 + body.len() as u32).to_le_bytes());
+        bytes.extend(b"WAVE");
+        bytes.extend(body);
+        bytes
+    }
+
+    fn fmt(tag: u16, channels: u16, bits: u16) -> Vec<u8> {
+        let block_align = channels * bits / 8;
+        let mut body = Vec::new();
+        body.extend(tag.to_le_bytes());
+        body.extend(channels.to_le_bytes());
+        body.extend(48000u32.to_le_bytes());
+        body.extend((48000 * block_align as u32).to_le_bytes());
+        body.extend(block_align.to_le_bytes());
+        body.extend(bits.to_le_bytes());
+        body
+    }
+
+    fn open(bytes: Vec<u8>) -> Result<WavReader<Cursor<Vec<u8>>>, DecodeError> {
+        WavReader::new(Cursor::new(bytes))
+    }
+
+    fn read_all(reader: &mut WavReader<Cursor<Vec<u8>>>) -> Vec<Vec<f32>> {
+        let channels = reader.metadata().channel_count as usize;
+        let mut all = vec![Vec::new(); channels];
+        let mut buffers = vec![Vec::new(); channels];
+        while reader.read(&mut buffers, 3).unwrap() > 0 {
+            for (all, buffer) in all.iter_mut().zip(&buffers) {
+                all.extend(buffer);
+            }
+        }
+        all
+    }
+
+    #[test]
+    fn decodes_integer_and_float_formats() {
+        let cases: [(u16, u16, Vec<u8>, Vec<f32>); 6] = [
+            (WAVE_FORMAT_PCM, 8, vec![0, 128, 192], vec![-1.0, 0.0, 0.5]),
+            (
+                WAVE_FORMAT_PCM,
+                16,
+                [i16::MIN, 0, 16384]
+                    .iter()
+                    .flat_map(|s| s.to_le_bytes())
+                    .collect(),
+                vec![-1.0, 0.0, 0.5],
+            ),
+            (
+                WAVE_FORMAT_PCM,
+                24,
+                vec![0x00, 0x00, 0x80, 0, 0, 0, 0x00, 0x00, 0x40],
+                vec![-1.0, 0.0, 0.5],
+            ),
+            (
+                WAVE_FORMAT_PCM,
+                32,
+                [i32::MIN, 0, 1 << 30]
+                    .iter()
+                    .flat_map(|s| s.to_le_bytes())
+                    .collect(),
+                vec![-1.0, 0.0, 0.5],
+            ),
+            (
+                WAVE_FORMAT_IEEE_FLOAT,
+                32,
+                [-0.75f32, 0.0, 0.25]
+                    .iter()
+                    .flat_map(|s| s.to_le_bytes())
+                    .collect(),
+                vec![-0.75, 0.0, 0.25],
+            ),
+            (
+                WAVE_FORMAT_IEEE_FLOAT,
+                64,
+                [-0.75f64, 0.0, 0.25]
+                    .iter()
+                    .flat_map(|s| s.to_le_bytes())
+                    .collect(),
+                vec![-0.75, 0.0, 0.25],
+            ),
+        ];
+        for (tag, bits, data, expected) in cases {
+            let mut reader = open(riff(&[
+                chunk(b"fmt ", &fmt(tag, 1, bits)),
+                chunk(b"data", &data),
+            ]))
+            .unwrap();
+            assert_eq!(reader.bits_per_sample(), bits);
+            assert_eq!(reader.metadata().frame_count, 3);
+            assert_eq!(read_all(&mut reader), [expected], "{} bits", bits);
+        }
+    }
+
+    #[test]
+    fn deinterleaves_channels() {
+        let data: Vec<u8> = [1i16, -1, 2, -2]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        let mut reader = open(riff(&[
+            chunk(b"fmt ", &fmt(WAVE_FORMAT_PCM, 2, 16)),
+            chunk(b"data", &data),
+        ]))
+        .unwrap();
+        let scale = 1.0 / 32768.0;
+        assert_eq!(
+            read_all(&mut reader),
+            [vec![scale, 2.0 * scale], vec![-scale, -2.0 * scale]]
+        );
+    }
+
+    #[test]
+    fn reads_extensible_format() {
+        // 24 valid bits in 32-bit containers, front left and right
+        let mut body = fmt(WAVE_FORMAT_EXTENSIBLE, 2, 32);
+        body.extend(22u16.to_le_bytes());
+        body.extend(24u16.to_le_bytes());
+        body.extend(0x3u32.to_le_bytes());
+        body.extend(WAVE_FORMAT_PCM.to_le_bytes());
+        body.extend(b"\x00\x00\x00\x00\x10\x00\x80\x00\x00\xaa\x00\x38\x9b\x71");
+        let data: Vec<u8> = [1 << 30, -(1 << 30)]
+            .iter()
+            .flat_map(|s: &i32| s.to_le_bytes())
+            .collect();
+        let mut reader = open(riff(&[chunk(b"fmt ", &body), chunk(b"data", &data)])).unwrap();
+        assert_eq!(reader.sample_format(), SampleFormat::I32);
+        assert_eq!(reader.bits_per_sample(), 24);
+        assert_eq!(reader.channel_mask(), Some(0x3));
+        assert_eq!(read_all(&mut reader), [vec![0.5], vec![-0.5]]);
+    }
+
+    #[test]
+    fn takes_rf64_data_size_from_ds64() {
+        let data: Vec<u8> = [0.5f32; 4].iter().flat_map(|s| s.to_le_bytes()).collect();
+        let mut ds64 = Vec::new();
+        ds64.extend(0u64.to_le_bytes()); // RIFF size
+        ds64.extend(12u64.to_le_bytes()); // data size: three of the four samples
+        ds64.extend(3u64.to_le_bytes()); // sample count
+        ds64.extend(0u32.to_le_bytes()); // table length
+        let mut data_chunk = b"data".to_vec();
+        data_chunk.extend(RF64_SIZE_PLACEHOLDER.to_le_bytes());
+        data_chunk.extend(&data);
+
+        let mut bytes = riff(&[
+            chunk(b"ds64", &ds64),
+            chunk(b"fmt ", &fmt(WAVE_FORMAT_IEEE_FLOAT, 1, 32)),
+            data_chunk,
+        ]);
+        bytes[..4].copy_from_slice(b"RF64");
+        bytes[4..8].copy_from_slice(&RF64_SIZE_PLACEHOLDER.to_le_bytes());
+        let mut reader = open(bytes).unwrap();
+        assert_eq!(reader.metadata().frame_count, 3);
+        assert_eq!(read_all(&mut reader), [vec![0.5; 3]]);
+    }
+
+    #[test]
+    fn truncated_data_ends_at_last_whole_frame() {
+        let mut bytes = riff(&[
+            chunk(b"fmt ", &fmt(WAVE_FORMAT_PCM, 2, 16)),
+            chunk(b"data", &[0; 64]),
+        ]);
+        // Cut mid-frame, well short of the declared 64 bytes
+        bytes.truncate(bytes.len() - 64 + 22);
+        let mut reader = open(bytes).unwrap();
+        assert_eq!(reader.metadata().frame_count, 5);
+        assert_eq!(read_all(&mut reader)[0].len(), 5);
+    }
+
+    #[test]
+    fn skips_unknown_and_odd_sized_chunks() {
+        let mut long_fmt = fmt(WAVE_FORMAT_PCM, 1, 16);
+        long_fmt.extend([0; 100]);
+        let mut reader = open(riff(&[
+            chunk(b"LIST", b"odd"),
+            chunk(b"fmt ", &long_fmt),
+            chunk(b"bext", &[0; 7]),
+            chunk(b"data", &[0, 0x40]),
+        ]))
+        .unwrap();
+        assert_eq!(read_all(&mut reader), [vec![0.5]]);
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        // A fmt chunk claiming 2 GB is refused without allocating it
+        let mut huge_fmt = chunk(b"fmt ", &fmt(WAVE_FORMAT_PCM, 1, 16));
+        huge_fmt[4..8].copy_from_slice(&0x7fff_fff0u32.to_le_bytes());
+        assert!(open(riff(&[huge_fmt])).is_err());
+
+        assert!(open(riff(&[chunk(b"fmt ", &[0; 8])])).is_err());
+        assert!(open(riff(&[chunk(b"data", &[0; 4])])).is_err());
+        assert!(open(riff(&[chunk(b"fmt ", &fmt(WAVE_FORMAT_PCM, 1, 16))])).is_err());
+        assert!(matches!(
+            open(riff(&[
+                chunk(b"fmt ", &fmt(0x0055, 1, 16)),
+                chunk(b"data", &[])
+            ])),
+            Err(DecodeError::Unsupported(_))
+        ));
+        assert!(open(b"RIFX\0\0\0\0WAVE".to_vec()).is_err());
+    }
+
+    #[test]
+    fn seeks_to_exact_frame() {
+        let data: Vec<u8> = (0..100i16).flat_map(|s| (s * 256).to_le_bytes()).collect();
+        let mut reader = open(riff(&[
+            chunk(b"fmt ", &fmt(WAVE_FORMAT_PCM, 1, 16)),
+            chunk(b"data", &data),
+        ]))
+        .unwrap();
+        let mut buffers = vec![Vec::new()];
+        reader.seek(37).unwrap();
+        assert_eq!(reader.position(), 37);
+        reader.read(&mut buffers, 2).unwrap();
+        assert_eq!(buffers[0], [37.0 / 128.0, 38.0 / 128.0]);
+
+        reader.seek(1000).unwrap();
+        assert_eq!(reader.position(), 100);
+        assert_eq!(reader.read(&mut buffers, 2).unwrap(), 0);
+    }
+
+    #[test]
+    fn reads_bundled_recording() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/אני פורים.wav");
+        let mut reader = WavReader::open(path).unwrap();
+        let metadata = reader.metadata();
+        assert_eq!(metadata.sample_rate, 44100);
+        assert_eq!(metadata.channel_count, 2);
+        assert_eq!(metadata.frame_count, 190042);
+
+        let mut buffers = vec![Vec::new(); 2];
+        reader.read(&mut buffers, 1).unwrap();
+        assert_eq!(buffers[0], [-1097.0 / 32768.0]);
+        assert_eq!(buffers[1], [-979.0 / 32768.0]);
+
+        let mut frames = 1;
+        while reader.read(&mut buffers, 4096).unwrap() > 0 {
+            frames += buffers[0].len() as u64;
+        }
+        assert_eq!(frames, metadata.frame_count);
+    }
+}
@@ -0,0 +1,304 @@
+use crate::viewport::{Column, Viewport, ViewportError, WaveformFrame};
+
+use std::ops::Range;
+
+/// How the vertex buffer is meant to be drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Primitive {
+    /// Filled waveform: two vertices at each column edge, lanes and gaps
+    /// joined with degenerate triangles.
+    TriangleStrip,
+    /// One vertical segment per column through its center.
+    LineList,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeometryOptions {
+    pub primitive: Primitive,
+    /// When set, every vertex gets a third attribute: `1.0` if its column
+    /// starts before this frame, `0.0` otherwise.
+    pub played_until: Option<u64>,
+    /// Emit an RMS band after the peak geometry.
+    pub include_rms: bool,
+}
+
+impl Default for GeometryOptions {
+    fn default() -> Self {
+        GeometryOptions {
+            primitive: Primitive::TriangleStrip,
+            played_until: None,
+            include_rms: true,
+        }
+    }
+}
+
+/// Flat vertex data ready to upload as-is.
+///
+/// Each vertex is `floats_per_vertex` consecutive `f32`s: `x, y` in normalized
+/// device coordinates (`-1..1`, y up), followed by the played flag when
+/// requested. `peaks` and `rms` are vertex ranges to draw separately.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Geometry {
+    pub primitive: Primitive,
+    pub floats_per_vertex: usize,
+    pub vertices: Vec<f32>,
+    pub peaks: Range<usize>,
+    pub rms: Range<usize>,
+}
+
+impl Geometry {
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len() / self.floats_per_vertex
+    }
+}
+
+impl WaveformFrame {
+    pub fn to_geometry(&self, options: &GeometryOptions) -> Geometry {
+        let mut builder = Builder {
+            frame: self,
+            options,
+            floats_per_vertex: if options.played_until.is_some() { 3 } else { 2 },
+            vertices: Vec::new(),
+            section_start: 0,
+            strip_open: false,
+        };
+
+        builder.emit(|c| (c.peak_top, c.peak_bottom));
+        let peaks = 0..builder.vertex_count();
+
+        if options.include_rms {
+            builder.section_start = peaks.end;
+            builder.emit(|c| (c.rms_top, c.rms_bottom));
+        }
+        let rms = peaks.end..builder.vertex_count();
+
+        Geometry {
+            primitive: options.primitive,
+            floats_per_vertex: builder.floats_per_vertex,
+            vertices: builder.vertices,
+            peaks,
+            rms,
+        }
+    }
+}
+
+impl Viewport<'_> {
+    pub fn render_geometry(
+        &self,
+        width_px: u32,
+        options: &GeometryOptions,
+    ) -> Result<Geometry, ViewportError> {
+        Ok(self.render(width_px)?.to_geometry(options))
+    }
+}
+
+struct Builder<'a> {
+    frame: &'a WaveformFrame,
+    options: &'a GeometryOptions,
+    floats_per_vertex: usize,
+    vertices: Vec<f32>,
+    // First vertex of the range being built; strips never bridge across it
+    section_start: usize,
+    // Whether the last strip segment can be continued without a degenerate join
+    strip_open: bool,
+}
+
+impl Builder<'_> {
+    fn vertex_count(&self) -> usize {
+        self.vertices.len() / self.floats_per_vertex
+    }
+
+    fn emit(&mut self, extent: impl Fn(&Column) -> (f32, f32)) {
+        let frame = self.frame;
+        for lane in &frame.lanes {
+            // Each lane starts a new strip segment
+            self.strip_open = false;
+            for (x, column) in lane.columns.iter().enumerate() {
+                if column.is_empty() {
+                    self.strip_open = false;
+                    continue;
+                }
+                let (top, bottom) = extent(column);
+                let played = self.played(column);
+                match self.options.primitive {
+                    Primitive::TriangleStrip => {
+                        let left = x as f32;
+                        let right = left + 1.0;
+                        self.strip_vertex(left, top, played);
+                        self.push(left, bottom, played);
+                        self.push(right, top, played);
+                        self.push(right, bottom, played);
+                        self.strip_open = true;
+                    }
+                    Primitive::LineList => {
+                        let center = x as f32 + 0.5;
+                        self.push(center, top, played);
+                        self.push(center, bottom, played);
+                    }
+                }
+            }
+        }
+    }
+
+    // First vertex of a column in a strip; bridges from the previous segment
+    // with two repeated vertices so the in-between triangles have zero area.
+    fn strip_vertex(&mut self, x: f32, y: f32, played: f32) {
+        if !self.strip_open && self.vertex_count() > self.section_start {
+            let last = self.vertices.len() - self.floats_per_vertex;
+            let previous = self.vertices[last..].to_vec();
+            self.vertices.extend_from_slice(&previous);
+            self.push(x, y, played);
+        }
+        self.push(x, y, played);
+    }
+
+    fn played(&self, column: &Column) -> f32 {
+        match self.options.played_until {
+            Some(frame) if column.start_frame < frame => 1.0,
+            _ => 0.0,
+        }
+    }
+
+    fn push(&mut self, x: f32, y: f32, played: f32) {
+        let width = self.frame.width.max(1) as f32;
+        let height = self.frame.height.max(1) as f32;
+        self.vertices.push(x / width * 2.0 - 1.0);
+        self.vertices.push(1.0 - y / height * 2.0);
+        if self.floats_per_vertex == 3 {
+            self.vertices.push(played);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peaks::PeakPyramid;
+    use crate::viewport::Lane;
+
+    fn column(start_frame: u64, peak: (f32, f32), rms: (f32, f32)) -> Column {
+        Column {
+            start_frame,
+            end_frame: start_frame + 1,
+            peak_top: peak.0,
+            peak_bottom: peak.1,
+            rms_top: rms.0,
+            rms_bottom: rms.1,
+        }
+    }
+
+    fn empty(start_frame: u64) -> Column {
+        Column {
+            end_frame: start_frame,
+            ..column(start_frame, (5.0, 5.0), (5.0, 5.0))
+        }
+    }
+
+    // Lanes of equal height over a 4 by 10 pixel frame
+    fn frame(lanes: Vec<Vec<Column>>) -> WaveformFrame {
+        let height = 10.0 / lanes.len() as f32;
+        WaveformFrame {
+            width: 4,
+            height: 10,
+            lanes: lanes
+                .into_iter()
+                .enumerate()
+                .map(|(index, columns)| Lane {
+                    top: index as f32 * height,
+                    height,
+                    columns,
+                })
+                .collect(),
+        }
+    }
+
+    fn full_lane() -> Vec<Column> {
+        (0..4).map(|x| column(x, (0.0, 10.0), (2.5, 7.5))).collect()
+    }
+
+    fn options(primitive: Primitive, include_rms: bool) -> GeometryOptions {
+        GeometryOptions {
+            primitive,
+            played_until: None,
+            include_rms,
+        }
+    }
+
+    #[test]
+    fn maps_columns_to_ndc() {
+        let frame = frame(vec![vec![
+            column(0, (0.0, 10.0), (0.0, 10.0)),
+            column(1, (5.0, 7.5), (5.0, 7.5)),
+        ]]);
+        let strip = frame.to_geometry(&options(Primitive::TriangleStrip, false));
+        #[rustfmt::skip]
+        let expected = [
+            -1.0, 1.0, -1.0, -1.0, -0.5, 1.0, -0.5, -1.0,
+            -0.5, 0.0, -0.5, -0.5, 0.0, 0.0, 0.0, -0.5,
+        ];
+        assert_eq!(strip.vertices, expected);
+
+        let lines = frame.to_geometry(&options(Primitive::LineList, false));
+        assert_eq!(
+            lines.vertices,
+            [-0.75, 1.0, -0.75, -1.0, -0.25, 0.0, -0.25, -0.5]
+        );
+    }
+
+    #[test]
+    fn vertex_counts_follow_the_primitive() {
+        let one = frame(vec![full_lane()]);
+        let strip = one.to_geometry(&options(Primitive::TriangleStrip, true));
+        assert_eq!((strip.peaks.clone(), strip.rms.clone()), (0..16, 16..32));
+        assert_eq!(strip.vertex_count(), 32);
+        let lines = one.to_geometry(&options(Primitive::LineList, true));
+        assert_eq!((lines.peaks, lines.rms), (0..8, 8..16));
+
+        // Two degenerate vertices join each lane or gap to the strip before it
+        let two = frame(vec![full_lane(), full_lane()]);
+        let strip = two.to_geometry(&options(Primitive::TriangleStrip, false));
+        assert_eq!((strip.peaks, strip.rms), (0..34, 34..34));
+        let mut gap = full_lane();
+        gap[1] = empty(1);
+        let strip = frame(vec![gap.clone()]).to_geometry(&options(Primitive::TriangleStrip, false));
+        assert_eq!(strip.vertex_count(), 3 * 4 + 2);
+        let lines = frame(vec![gap]).to_geometry(&options(Primitive::LineList, false));
+        assert_eq!(lines.vertex_count(), 3 * 2);
+    }
+
+    #[test]
+    fn played_flag_splits_at_the_playhead() {
+        let frame = frame(vec![full_lane()]);
+        let geometry = frame.to_geometry(&GeometryOptions {
+            primitive: Primitive::LineList,
+            played_until: Some(2),
+            include_rms: false,
+        });
+        assert_eq!(geometry.floats_per_vertex, 3);
+        let played: Vec<f32> = geometry.vertices.chunks(3).map(|v| v[2]).collect();
+        assert_eq!(played, [1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+
+        let geometry = frame.to_geometry(&GeometryOptions {
+            played_until: Some(2),
+            ..GeometryOptions::default()
+        });
+        let played: Vec<f32> = geometry.vertices[geometry.peaks.start * 3..geometry.peaks.end * 3]
+            .chunks(3)
+            .map(|v| v[2])
+            .collect();
+        assert_eq!(played, [[1.0; 8], [0.0; 8]].concat());
+    }
+
+    #[test]
+    fn empty_viewport_has_no_vertices() {
+        let peaks = PeakPyramid::new(2, 4);
+        let geometry = Viewport::new(&peaks, 10)
+            .render_geometry(4, &GeometryOptions::default())
+            .unwrap();
+        assert!(geometry.vertices.is_empty());
+        assert_eq!((geometry.peaks, geometry.rms), (0..0, 0..0));
+
+        let geometry = frame(vec![vec![]]).to_geometry(&GeometryOptions::default());
+        assert_eq!(geometry.vertex_count(), 0);
+    }
+}
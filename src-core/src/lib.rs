@@ -1,3 +1,30 @@
+pub mod audiowaveform;
+pub mod decode;
+pub mod geometry;
+pub mod peakfile;
+pub mod peaks;
 pub mod player;
+pub mod playhead;
+pub mod pyramid;
+pub mod raster;
+pub mod rms;
+pub mod stretch;
+pub mod viewport;
 
-pub use player::{Metadata, PlaybackListener, PlaybackState, Player, PlayerError};
+pub use audiowaveform::{Audiowaveform, ExportOptions};
+pub use decode::{DecodeError, Decoder, WavReader};
+pub use geometry::{Geometry, GeometryOptions, Primitive};
+pub use peakfile::{PeaksFile, PeaksFileError};
+pub use peaks::{Peak, PeakPyramid};
+pub use player::{
+    Metadata, PlaybackListener, PlaybackState, Player, PlayerError, PlayerErrorKind, RateMode,
+    db_to_linear, linear_to_db,
+};
+pub use playhead::{PlayheadEstimator, PllConfig};
+pub use pyramid::{Bin, Pyramid};
+pub use raster::{Image, RasterStyle, Rgba};
+pub use rms::{Rms, RmsEnvelope};
+pub use stretch::{Stretch, time_stretch};
+pub use viewport::{
+    AmplitudeScale, ChannelLayout, Column, Lane, Viewport, ViewportError, WaveformFrame, Zoom,
+};
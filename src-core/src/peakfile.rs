@@ -0,0 +1,495 @@
+use crate::peaks::{Peak, PeakPyramid};
+use crate::pyramid::{Bin, Pyramid};
+use crate::rms::{Rms, RmsEnvelope};
+
+use std::fmt;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use xxhash_rust::xxh3::Xxh3;
+
+const MAGIC: &[u8; 4] = b"DWPK";
+const VERSION: u16 = 1;
+
+const FLAG_RMS: u32 = 1;
+
+#[derive(Debug)]
+pub enum PeaksFileError {
+    Io(io::Error),
+    /// Not a peaks file, or its contents are inconsistent.
+    InvalidFormat(String),
+    /// Written with a format version this build cannot read.
+    UnsupportedVersion(i64),
+    /// The peaks were computed from different audio than the file being opened.
+    SourceMismatch {
+        expected: u64,
+        found: u64,
+    },
+}
+
+impl fmt::Display for PeaksFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeaksFileError::Io(e) => write!(f, "I/O error: {}", e),
+            PeaksFileError::InvalidFormat(message) => write!(f, "Invalid peaks file: {}", message),
+            PeaksFileError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported peaks file version {}", version)
+            }
+            PeaksFileError::SourceMismatch { expected, found } => write!(
+                f,
+                "Peaks file is stale: source hash {:016x} does not match audio {:016x}",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PeaksFileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PeaksFileError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PeaksFileError {
+    fn from(e: io::Error) -> Self {
+        PeaksFileError::Io(e)
+    }
+}
+
+/// Hash identifying the audio a peaks file was built from (XXH3 of the file bytes).
+pub fn source_hash<R: Read>(mut reader: R) -> io::Result<u64> {
+    let mut hasher = Xxh3::new();
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(hasher.digest());
+        }
+        hasher.update(&buffer[..read]);
+    }
+}
+
+/// Precomputed analysis of one audio file, as stored on disk.
+///
+/// Layout, little-endian: a 48-byte header (`DWPK`, version, flags, sample
+/// rate, channel count, frame count, base bin size, source hash, level count)
+/// followed by the peak pyramid and, if flagged, the RMS envelope. Each
+/// pyramid is stored channel by channel, level by level, as a bin count and
+/// the bins, then the trailing partial bin of every channel.
+#[derive(Debug, Clone)]
+pub struct PeaksFile {
+    pub sample_rate: u32,
+    pub source_hash: u64,
+    pub peaks: PeakPyramid,
+    pub rms: Option<RmsEnvelope>,
+}
+
+impl PeaksFile {
+    pub fn save<W: Write>(&self, writer: W) -> Result<(), PeaksFileError> {
+        let mut w = BufWriter::new(writer);
+        let peaks = &self.peaks;
+        if let Some(rms) = &self.rms
+            && (rms.channel_count() != peaks.channel_count()
+                || rms.base_bin_frames() != peaks.base_bin_frames()
+                || rms.frame_count() != peaks.frame_count())
+        {
+            return Err(PeaksFileError::InvalidFormat(
+                "RMS envelope does not line up with peaks".to_string(),
+            ));
+        }
+
+        w.write_all(MAGIC)?;
+        w.write_all(&VERSION.to_le_bytes())?;
+        w.write_all(&0u16.to_le_bytes())?;
+        let flags = if self.rms.is_some() { FLAG_RMS } else { 0 };
+        w.write_all(&flags.to_le_bytes())?;
+        w.write_all(&self.sample_rate.to_le_bytes())?;
+        w.write_all(&(peaks.channel_count() as u32).to_le_bytes())?;
+        w.write_all(&peaks.frame_count().to_le_bytes())?;
+        w.write_all(&peaks.base_bin_frames().to_le_bytes())?;
+        w.write_all(&self.source_hash.to_le_bytes())?;
+        w.write_all(&(peaks.level_count() as u32).to_le_bytes())?;
+
+        write_pyramid(&mut w, peaks, |w, peak| {
+            w.write_all(&peak.min.to_le_bytes())?;
+            w.write_all(&peak.max.to_le_bytes())
+        })?;
+        if let Some(rms) = &self.rms {
+            write_pyramid(&mut w, rms, |w, bin| {
+                w.write_all(&bin.sum_squares.to_le_bytes())?;
+                w.write_all(&bin.count.to_le_bytes())
+            })?;
+        }
+        w.flush()?;
+        Ok(())
+    }
+
+    pub fn load<R: Read>(reader: R) -> Result<Self, PeaksFileError> {
+        PeaksFile::parse(BufReader::new(reader))
+    }
+
+    fn parse<R: Read>(mut r: R) -> Result<Self, PeaksFileError> {
+        let mut magic = [0u8; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(PeaksFileError::InvalidFormat(
+                "missing DWPK header".to_string(),
+            ));
+        }
+        let version = read_u16(&mut r)?;
+        if version != VERSION {
+            return Err(PeaksFileError::UnsupportedVersion(version.into()));
+        }
+        let _reserved = read_u16(&mut r)?;
+        let flags = read_u32(&mut r)?;
+        let sample_rate = read_u32(&mut r)?;
+        let channel_count = read_u32(&mut r)? as usize;
+        let frame_count = read_u64(&mut r)?;
+        let base_bin_frames = read_u64(&mut r)?;
+        let source_hash = read_u64(&mut r)?;
+        let level_count = read_u32(&mut r)? as usize;
+
+        if channel_count == 0 || base_bin_frames == 0 || level_count == 0 || level_count > 64 {
+            return Err(PeaksFileError::InvalidFormat(
+                "zero channels, bin size or levels".to_string(),
+            ));
+        }
+
+        let peaks: PeakPyramid =
+            read_pyramid(&mut r, channel_count, level_count, base_bin_frames, |r| {
+                Ok(Peak {
+                    min: f32::from_le_bytes(read_array(r)?),
+                    max: f32::from_le_bytes(read_array(r)?),
+                })
+            })?;
+        if peaks.frame_count() != frame_count {
+            return Err(PeaksFileError::InvalidFormat(format!(
+                "header says {} frames, bins cover {}",
+                frame_count,
+                peaks.frame_count()
+            )));
+        }
+
+        let rms = if flags & FLAG_RMS != 0 {
+            let rms: RmsEnvelope =
+                read_pyramid(&mut r, channel_count, level_count, base_bin_frames, |r| {
+                    Ok(Rms {
+                        sum_squares: f64::from_le_bytes(read_array(r)?),
+                        count: u64::from_le_bytes(read_array(r)?),
+                    })
+                })?;
+            if rms.frame_count() != frame_count {
+                return Err(PeaksFileError::InvalidFormat(
+                    "RMS envelope length differs from peaks".to_string(),
+                ));
+            }
+            Some(rms)
+        } else {
+            None
+        };
+
+        Ok(PeaksFile {
+            sample_rate,
+            source_hash,
+            peaks,
+            rms,
+        })
+    }
+
+    /// Like `load`, but fails with `SourceMismatch` unless the file was built
+    /// from audio hashing to `expected_hash`.
+    pub fn load_verified<R: Read>(reader: R, expected_hash: u64) -> Result<Self, PeaksFileError> {
+        let file = PeaksFile::load(reader)?;
+        file.verify(expected_hash)?;
+        Ok(file)
+    }
+
+    /// Loads `path` through a memory map rather than buffered reads, optionally verifying its hash.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_mapped(
+        path: impl AsRef<std::path::Path>,
+        expected_hash: Option<u64>,
+    ) -> Result<Self, PeaksFileError> {
+        let file = std::fs::File::open(path)?;
+        // Safety: the mapping is only read while parsing and dropped before
+        // returning; concurrent truncation would surface as SIGBUS, as with
+        // any mmap reader.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        let peaks = PeaksFile::parse(&map[..])?;
+        if let Some(hash) = expected_hash {
+            peaks.verify(hash)?;
+        }
+        Ok(peaks)
+    }
+
+    pub fn verify(&self, expected_hash: u64) -> Result<(), PeaksFileError> {
+        if self.source_hash != expected_hash {
+            return Err(PeaksFileError::SourceMismatch {
+                expected: expected_hash,
+                found: self.source_hash,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn write_pyramid<W: Write, B: Bin>(
+    w: &mut W,
+    pyramid: &Pyramid<B>,
+    write_bin: impl Fn(&mut W, &B) -> io::Result<()>,
+) -> io::Result<()> {
+    for channel in 0..pyramid.channel_count() {
+        for level in 0..pyramid.level_count() {
+            let bins = pyramid.level(channel, level);
+            w.write_all(&(bins.len() as u64).to_le_bytes())?;
+            for bin in bins {
+                write_bin(w, bin)?;
+            }
+        }
+    }
+    w.write_all(&pyramid.pending_frames().to_le_bytes())?;
+    for channel in 0..pyramid.channel_count() {
+        write_bin(w, &pyramid.pending(channel))?;
+    }
+    Ok(())
+}
+
+fn read_pyramid<R: Read, B: Bin>(
+    r: &mut R,
+    channel_count: usize,
+    level_count: usize,
+    base_bin_frames: u64,
+    read_bin: impl Fn(&mut R) -> io::Result<B>,
+) -> Result<Pyramid<B>, PeaksFileError> {
+    // Channels and bins are only counted by the header, so the vectors grow as
+    // they arrive and a corrupt count runs out of data instead of memory
+    let mut levels = Vec::with_capacity(channel_count.min(64));
+    for _ in 0..channel_count {
+        let mut channel = Vec::with_capacity(level_count);
+        for _ in 0..level_count {
+            let len = read_u64(r)?;
+            let mut bins = Vec::with_capacity(len.min(1 << 20) as usize);
+            for _ in 0..len {
+                bins.push(read_bin(r)?);
+            }
+            channel.push(bins);
+        }
+        levels.push(channel);
+    }
+    let pending_frames = read_u64(r)?;
+    let pending = (0..channel_count)
+        .map(|_| read_bin(r))
+        .collect::<io::Result<Vec<B>>>()?;
+
+    Pyramid::from_parts(base_bin_frames, levels, pending, pending_frames)
+        .ok_or_else(|| PeaksFileError::InvalidFormat("level sizes are inconsistent".to_string()))
+}
+
+fn read_array<const N: usize, R: Read>(r: &mut R) -> io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    r.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_u16<R: Read>(r: &mut R) -> io::Result<u16> {
+    Ok(u16::from_le_bytes(read_array(r)?))
+}
+
+fn read_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    Ok(u32::from_le_bytes(read_array(r)?))
+}
+
+fn read_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    Ok(u64::from_le_bytes(read_array(r)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two channels of 1000 frames in bins of 64, so the last bin is partial
+    fn peaks_file(with_rms: bool) -> PeaksFile {
+        let channels: Vec<Vec<f32>> = (0..2)
+            .map(|c| {
+                (0..1000)
+                    .map(|i| ((i * (c + 3)) as f32 * 0.01).sin())
+                    .collect()
+            })
+            .collect();
+        let mut peaks = PeakPyramid::new(2, 64);
+        peaks.append(&channels);
+        let rms = with_rms.then(|| {
+            let mut rms = RmsEnvelope::new(2, 64);
+            rms.append(&channels);
+            rms
+        });
+        PeaksFile {
+            sample_rate: 48000,
+            source_hash: 0x1234_5678_9abc_def0,
+            peaks,
+            rms,
+        }
+    }
+
+    fn saved(file: &PeaksFile) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        file.save(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn assert_same<B: Bin + PartialEq + fmt::Debug>(a: &Pyramid<B>, b: &Pyramid<B>) {
+        assert_eq!(a.frame_count(), b.frame_count());
+        assert_eq!(a.base_bin_frames(), b.base_bin_frames());
+        assert_eq!(a.level_count(), b.level_count());
+        assert_eq!(a.pending_frames(), b.pending_frames());
+        for channel in 0..a.channel_count() {
+            for level in 0..a.level_count() {
+                assert_eq!(a.level(channel, level), b.level(channel, level));
+            }
+            assert_eq!(a.pending(channel), b.pending(channel));
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        for with_rms in [false, true] {
+            let file = peaks_file(with_rms);
+            let loaded = PeaksFile::load(&saved(&file)[..]).unwrap();
+            assert_eq!(loaded.sample_rate, 48000);
+            assert_eq!(loaded.source_hash, file.source_hash);
+            assert_same(&loaded.peaks, &file.peaks);
+            assert_eq!(loaded.rms.is_some(), with_rms);
+            if let (Some(loaded), Some(rms)) = (&loaded.rms, &file.rms) {
+                assert_same(loaded, rms);
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn loads_mapped_file() {
+        let file = peaks_file(true);
+        let path = std::env::temp_dir().join(format!("driftwave-{}.dwpk", std::process::id()));
+        std::fs::write(&path, saved(&file)).unwrap();
+        let loaded = PeaksFile::load_mapped(&path, Some(file.source_hash));
+        let stale = PeaksFile::load_mapped(&path, Some(42));
+        std::fs::remove_file(&path).unwrap();
+
+        let loaded = loaded.unwrap();
+        assert_same(&loaded.peaks, &file.peaks);
+        assert_same(loaded.rms.as_ref().unwrap(), file.rms.as_ref().unwrap());
+        assert!(matches!(stale, Err(PeaksFileError::SourceMismatch { .. })));
+    }
+
+    #[test]
+    fn rejects_stale_source_hash() {
+        let file = peaks_file(false);
+        let bytes = saved(&file);
+        assert!(PeaksFile::load_verified(&bytes[..], file.source_hash).is_ok());
+        match PeaksFile::load_verified(&bytes[..], 42) {
+            Err(PeaksFileError::SourceMismatch { expected, found }) => {
+                assert_eq!(expected, 42);
+                assert_eq!(found, file.source_hash);
+            }
+            other => panic!("expected SourceMismatch, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let bytes = saved(&peaks_file(true));
+
+        let mut magic = bytes.clone();
+        magic[0] = b'X';
+        assert!(matches!(
+            PeaksFile::load(&magic[..]),
+            Err(PeaksFileError::InvalidFormat(_))
+        ));
+
+        let mut version = bytes.clone();
+        version[4..6].copy_from_slice(&7u16.to_le_bytes());
+        assert!(matches!(
+            PeaksFile::load(&version[..]),
+            Err(PeaksFileError::UnsupportedVersion(7))
+        ));
+
+        let mut levels = bytes.clone();
+        levels[44..48].copy_from_slice(&65u32.to_le_bytes());
+        assert!(matches!(
+            PeaksFile::load(&levels[..]),
+            Err(PeaksFileError::InvalidFormat(_))
+        ));
+
+        let mut frames = bytes.clone();
+        frames[20..28].copy_from_slice(&999u64.to_le_bytes());
+        assert!(matches!(
+            PeaksFile::load(&frames[..]),
+            Err(PeaksFileError::InvalidFormat(_))
+        ));
+
+        assert!(matches!(
+            PeaksFile::load(&bytes[..bytes.len() - 1]),
+            Err(PeaksFileError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn corrupt_bin_count_fails_without_huge_allocation() {
+        // The first level's bin count, just after the header
+        let mut bytes = saved(&peaks_file(false));
+        bytes[48..56].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            PeaksFile::load(&bytes[..]),
+            Err(PeaksFileError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn corrupt_channel_count_fails_without_huge_allocation() {
+        let mut bytes = saved(&peaks_file(false));
+        bytes[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(
+            PeaksFile::load(&bytes[..]),
+            Err(PeaksFileError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn from_parts_checks_level_sizes() {
+        let bins = |len: usize| {
+            vec![
+                Peak {
+                    min: -1.0,
+                    max: 1.0
+                };
+                len
+            ]
+        };
+        let parts = |levels: Vec<Vec<Peak>>, pending_frames: u64| {
+            PeakPyramid::from_parts(64, vec![levels], vec![Peak::EMPTY], pending_frames)
+        };
+
+        let pyramid = parts(vec![bins(5), bins(2), bins(1)], 10).unwrap();
+        assert_eq!(pyramid.frame_count(), 5 * 64 + 10);
+
+        // Parent level of the wrong size
+        assert!(parts(vec![bins(5), bins(3), bins(1)], 0).is_none());
+        // Top level with two bins and no parent
+        assert!(parts(vec![bins(4), bins(2)], 0).is_none());
+        // Pending frames filling a whole bin
+        assert!(parts(vec![bins(1)], 64).is_none());
+        // Channels of different lengths
+        assert!(
+            PeakPyramid::from_parts(
+                64,
+                vec![vec![bins(1)], vec![bins(0)]],
+                vec![Peak::EMPTY; 2],
+                0
+            )
+            .is_none()
+        );
+        // Pending bins for the wrong number of channels
+        assert!(PeakPyramid::from_parts(64, vec![vec![bins(1)]], vec![], 0).is_none());
+    }
+}
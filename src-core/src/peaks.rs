@@ -0,0 +1,52 @@
+use crate::pyramid::{Bin, Pyramid};
+
+/// Minimum and maximum sample value over a run of frames.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Peak {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Peak {
+    /// Identity for `merge`: covers no samples.
+    pub const EMPTY: Peak = Peak {
+        min: f32::INFINITY,
+        max: f32::NEG_INFINITY,
+    };
+
+    pub fn is_empty(&self) -> bool {
+        self.min > self.max
+    }
+
+    pub fn merge(self, other: Peak) -> Peak {
+        Peak {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
+
+impl Bin for Peak {
+    fn add_sample(&mut self, sample: f32) {
+        // NaN compares false both ways and is skipped
+        if sample < self.min {
+            self.min = sample;
+        }
+        if sample > self.max {
+            self.max = sample;
+        }
+    }
+
+    fn merge(self, other: Peak) -> Peak {
+        Peak::merge(self, other)
+    }
+}
+
+impl Default for Peak {
+    fn default() -> Self {
+        Peak::EMPTY
+    }
+}
+
+/// Min/max pyramid; see [`Pyramid`].
+pub type PeakPyramid = Pyramid<Peak>;
@@ -11,6 +11,13 @@ pub trait Player {
 
     async fn load(&mut self, source: &str) -> Result<Self::Sound, PlayerError>;
 
+    /// Loads a sound from the bytes of an encoded audio file, copying them.
+    async fn load_bytes(&mut self, bytes: &[u8]) -> Result<Self::Sound, PlayerError>;
+
+    /// Like `load_bytes`, but hands the bytes over so a backend that can play
+    /// from them in place avoids the copy.
+    async fn load_owned(&mut self, bytes: Vec<u8>) -> Result<Self::Sound, PlayerError>;
+
     fn play_from(
         &mut self,
         sound: &mut Self::Sound,
@@ -26,8 +33,54 @@ pub trait Player {
         listener: Option<Self::PlaybackListener>,
     ) -> Result<Self::Playback, PlayerError>;
 
+    /// Plays from `start_frame`, repeating `loop_start..loop_end` until paused
+    /// or dropped. Loop boundaries are sample accurate.
+    fn play_loop(
+        &mut self,
+        sound: &mut Self::Sound,
+        loop_start: u64,
+        loop_end: u64,
+        start_frame: u64,
+        listener: Option<Self::PlaybackListener>,
+    ) -> Result<Self::Playback, PlayerError>;
+
     fn pause(&mut self, playback: &mut Self::Playback) -> Result<u64, PlayerError>;
 
+    /// Continues a paused playback from where it stopped; range playbacks
+    /// still end at their original end frame. Resuming a playback that is
+    /// already playing does nothing and notifies no listener. A playback that
+    /// has finished or stopped cannot be resumed and fails with `InvalidState`.
+    fn resume(&mut self, playback: &mut Self::Playback) -> Result<(), PlayerError>;
+
+    /// Moves an existing playback to `frame`, keeping its listener, range end
+    /// and paused or playing state.
+    fn seek(&mut self, playback: &mut Self::Playback, frame: u64) -> Result<(), PlayerError>;
+
+    /// Sets the gain of `playback` as a linear factor, 1.0 being unity. Changes
+    /// are ramped so dragging a slider does not produce zipper noise.
+    fn set_volume(&mut self, playback: &mut Self::Playback, volume: f32)
+    -> Result<(), PlayerError>;
+
+    /// Sets the gain of `playback` in decibels, 0.0 being unity.
+    fn set_volume_db(&mut self, playback: &mut Self::Playback, db: f32) -> Result<(), PlayerError> {
+        self.set_volume(playback, db_to_linear(db))
+    }
+
+    /// Silences `playback` without forgetting its volume.
+    fn set_muted(&mut self, playback: &mut Self::Playback, muted: bool) -> Result<(), PlayerError>;
+
+    /// Sets stereo pan from -1.0 (left) through 0.0 (center) to 1.0 (right).
+    fn set_pan(&mut self, playback: &mut Self::Playback, pan: f32) -> Result<(), PlayerError>;
+
+    /// Plays `playback` at `rate` times normal speed. Positions from `pause`
+    /// and listeners stay in source frames whatever the rate.
+    fn set_playback_rate(
+        &mut self,
+        playback: &mut Self::Playback,
+        rate: f64,
+        mode: RateMode,
+    ) -> Result<(), PlayerError>;
+
     fn get_metadata(&mut self, sound: &mut Self::Sound) -> Result<Metadata, PlayerError>;
 
     fn get_state(&mut self, playback: &mut Self::Playback) -> Result<PlaybackState, PlayerError>;
@@ -37,19 +90,87 @@ pub trait Player {
     }
 }
 
+pub fn db_to_linear(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+pub fn linear_to_db(volume: f32) -> f32 {
+    20.0 * volume.log10()
+}
+
+/// Receives notifications about one playback.
+///
+/// Lifecycle callbacks run on the thread driving the player: for FMOD, inside
+/// the `Player` call that caused them or `FmodPlayer::update`; on the web, the
+/// main thread. `on_progress` and `on_looped` are detected while mixing and
+/// may instead run on the backend's audio thread.
 pub trait PlaybackListener: Send {
     fn on_progress(&mut self, position_frames: u64);
+
+    fn on_started(&mut self) {}
+
+    fn on_paused(&mut self, _position_frames: u64) {}
+
+    /// The playback reached the end of its sound or range.
+    fn on_finished(&mut self) {}
+
+    /// A looping playback wrapped from its loop end back to its loop start.
+    fn on_looped(&mut self) {}
+
+    /// The playback failed or was cut short outside of any call that could
+    /// return the error.
+    fn on_error(&mut self, _error: &PlayerError) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateMode {
+    /// Pitch follows speed, as when changing tape speed.
+    Varispeed,
+    /// Pitch is kept by time-stretching.
+    PreservePitch,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum PlaybackState {
     Playing,
-    NotPlaying,
+    /// Paused by `pause`; `resume` continues it.
+    Paused,
+    /// Reached the end of the sound or of its range.
+    Finished,
+    /// Ended early by the backend, e.g. its voice was stolen or its sound
+    /// was released.
+    Stopped,
+    /// The backend no longer knows about this playback.
     Invalid,
 }
 
+/// What went wrong, for callers that react to errors rather than show them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerErrorKind {
+    /// The player was used before a successful `init`.
+    NotInitialized,
+    FileNotFound,
+    /// The backend has no decoder for the file's format.
+    UnsupportedFormat,
+    /// The format is supported but the data is corrupt or truncated.
+    DecodeFailed,
+    /// A frame, range or parameter is out of bounds.
+    InvalidRange,
+    /// An argument is malformed, such as a path the backend cannot represent.
+    InvalidArgument,
+    /// The call is not valid in the current state of the player or playback.
+    InvalidState,
+    /// No output device could be opened, or it went away.
+    DeviceUnavailable,
+    /// Anything else, with the backend's own error code.
+    BackendSpecific {
+        code: i32,
+    },
+}
+
 #[derive(Debug)]
 pub struct PlayerError {
+    pub kind: PlayerErrorKind,
     pub message: String,
 }
 
@@ -61,8 +182,12 @@ impl fmt::Display for PlayerError {
 
 impl std::error::Error for PlayerError {}
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Metadata {
     pub sample_rate: u32,
     pub channel_count: u32,
+    /// Length in frames. A decoder reports 0 when the stream does not record
+    /// its length, as with an MP3 without a Xing header, so 0 does not by
+    /// itself mean the sound is empty.
     pub frame_count: u64,
 }
@@ -0,0 +1,263 @@
+use std::f64::consts::PI;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PllConfig {
+    /// Loop bandwidth in Hz. Lower values smooth harder but follow clock drift more slowly.
+    pub bandwidth_hz: f64,
+    /// Damping ratio of the loop; 0.707 gives a maximally flat response.
+    pub damping: f64,
+    /// Observations further than this from the prediction are treated as a
+    /// seek and snapped to instead of smoothed.
+    pub snap_threshold_seconds: f64,
+}
+
+impl Default for PllConfig {
+    fn default() -> Self {
+        PllConfig {
+            bandwidth_hz: 2.0,
+            damping: std::f64::consts::FRAC_1_SQRT_2,
+            snap_threshold_seconds: 0.25,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Lock {
+    time: f64,
+    position: f64,
+    rate: f64,
+}
+
+/// Smooths jittery playback progress into a steady playhead.
+///
+/// A second-order phase-locked loop tracks position and rate from
+/// `(wall-clock seconds, frame)` observations, so a renderer can ask for the
+/// position at any timestamp between them. Estimates never move backwards
+/// except across a seek. Observations only steer the estimate; it advances
+/// between them once `set_playing` has started it.
+#[derive(Debug, Clone)]
+pub struct PlayheadEstimator {
+    config: PllConfig,
+    sample_rate: f64,
+    // Frames per second of wall-clock time at the current playback rate
+    nominal_rate: f64,
+    lock: Option<Lock>,
+    playing: bool,
+    last_estimate: u64,
+}
+
+impl PlayheadEstimator {
+    pub fn new(sample_rate: u32, config: PllConfig) -> Self {
+        PlayheadEstimator {
+            config,
+            sample_rate: sample_rate as f64,
+            nominal_rate: sample_rate as f64,
+            lock: None,
+            playing: false,
+            last_estimate: 0,
+        }
+    }
+
+    pub fn config(&self) -> &PllConfig {
+        &self.config
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Feeds a reported `frame` observed at wall-clock `time` (seconds).
+    pub fn observe(&mut self, time: f64, frame: u64) {
+        let frame_f = frame as f64;
+        let Some(lock) = self.lock.as_mut() else {
+            self.seek(time, frame);
+            return;
+        };
+
+        let dt = (time - lock.time).max(0.0);
+        let predicted = if self.playing {
+            lock.position + lock.rate * dt
+        } else {
+            lock.position
+        };
+        let error = frame_f - predicted;
+
+        if error.abs() > self.config.snap_threshold_seconds * self.nominal_rate {
+            self.seek(time, frame);
+            return;
+        }
+
+        let omega = 2.0 * PI * self.config.bandwidth_hz;
+        let alpha = (2.0 * self.config.damping * omega * dt).min(1.0);
+        let beta = omega * omega * dt;
+
+        lock.position = predicted + alpha * error;
+        lock.rate =
+            (lock.rate + beta * error).clamp(self.nominal_rate * 0.5, self.nominal_rate * 2.0);
+        lock.time = time;
+    }
+
+    /// Jumps to `frame` without smoothing, e.g. after a user seek.
+    pub fn seek(&mut self, time: f64, frame: u64) {
+        self.lock = Some(Lock {
+            time,
+            position: frame as f64,
+            rate: self.nominal_rate,
+        });
+        self.last_estimate = frame;
+    }
+
+    /// Follows a change of playback speed at `time`, e.g. 2.0 for double
+    /// speed. The loop tracks rates from half to twice the speed set here.
+    pub fn set_playback_rate(&mut self, time: f64, rate: f64) {
+        assert!(rate > 0.0 && rate.is_finite(), "rate must be positive");
+        let nominal_rate = self.sample_rate * rate;
+        if let Some(lock) = self.lock.as_mut() {
+            if self.playing {
+                lock.position += lock.rate * (time - lock.time).max(0.0);
+            }
+            lock.time = time;
+            lock.rate = nominal_rate;
+        }
+        self.nominal_rate = nominal_rate;
+    }
+
+    /// Freezes or resumes the estimate at `time`.
+    pub fn set_playing(&mut self, time: f64, playing: bool) {
+        if playing == self.playing {
+            return;
+        }
+        if let Some(lock) = self.lock.as_mut() {
+            if self.playing {
+                lock.position += lock.rate * (time - lock.time).max(0.0);
+            }
+            lock.time = time;
+        }
+        self.playing = playing;
+    }
+
+    /// Smoothed frame position at wall-clock `time`.
+    pub fn estimate(&mut self, time: f64) -> u64 {
+        let Some(lock) = self.lock else {
+            return self.last_estimate;
+        };
+        let position = if self.playing {
+            lock.position + lock.rate * (time - lock.time)
+        } else {
+            lock.position
+        };
+        let frame = position.max(0.0) as u64;
+        self.last_estimate = self.last_estimate.max(frame);
+        self.last_estimate
+    }
+
+    pub fn reset(&mut self) {
+        self.lock = None;
+        self.playing = false;
+        self.last_estimate = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48000;
+
+    // Observations every 20 ms of audio advancing at `speed`, from `start` seconds
+    fn feed(estimator: &mut PlayheadEstimator, start: f64, speed: f64, count: usize) {
+        for i in 0..count {
+            let time = start + i as f64 * 0.02;
+            let frame = (time * speed * SAMPLE_RATE as f64) as u64;
+            estimator.observe(time, frame);
+        }
+    }
+
+    fn playing() -> PlayheadEstimator {
+        let mut estimator = PlayheadEstimator::new(SAMPLE_RATE, PllConfig::default());
+        estimator.set_playing(0.0, true);
+        estimator
+    }
+
+    #[test]
+    fn observations_do_not_start_playback() {
+        let mut estimator = PlayheadEstimator::new(SAMPLE_RATE, PllConfig::default());
+        estimator.observe(1.0, 48000);
+        estimator.observe(1.5, 72000);
+        assert!(!estimator.is_playing());
+        assert_eq!(estimator.estimate(2.0), estimator.estimate(3.0));
+
+        estimator.set_playing(2.0, true);
+        let paused = estimator.estimate(2.0);
+        assert!(estimator.estimate(3.0) > paused);
+    }
+
+    #[test]
+    fn locks_onto_a_drifting_clock() {
+        // The audio clock runs 0.5% fast against the wall clock
+        let mut estimator = playing();
+        feed(&mut estimator, 0.0, 1.005, 500);
+        let expected = 10.0 * 1.005 * SAMPLE_RATE as f64;
+        let error = estimator.estimate(10.0) as f64 - expected;
+        assert!(error.abs() < 10.0, "off by {} frames", error);
+    }
+
+    #[test]
+    fn smooths_jittery_reports() {
+        // Reports land up to 8 ms early or late; on average the estimate
+        // strays well under half that
+        let mut estimator = playing();
+        let (mut jitter_total, mut error_total) = (0.0, 0.0);
+        for i in 0..500u64 {
+            let time = i as f64 * 0.02;
+            let jitter = ((i * 7919) % 17) as f64 / 16.0 * 0.016 - 0.008;
+            estimator.observe(time, ((time + jitter) * SAMPLE_RATE as f64) as u64);
+            if i >= 100 {
+                let expected = (time + 0.01) * SAMPLE_RATE as f64;
+                error_total += (estimator.estimate(time + 0.01) as f64 - expected).abs();
+                jitter_total += (jitter * SAMPLE_RATE as f64).abs();
+            }
+        }
+        assert!(
+            error_total < jitter_total / 2.0,
+            "mean error {} frames against mean jitter {}",
+            error_total / 400.0,
+            jitter_total / 400.0
+        );
+    }
+
+    #[test]
+    fn snaps_to_a_seek() {
+        let mut estimator = playing();
+        feed(&mut estimator, 0.0, 1.0, 50);
+        estimator.observe(1.0, 10 * SAMPLE_RATE as u64);
+        assert_eq!(estimator.estimate(1.0), 10 * SAMPLE_RATE as u64);
+        assert!(estimator.is_playing());
+
+        // A small error is smoothed rather than snapped to
+        let mut estimator = playing();
+        feed(&mut estimator, 0.0, 1.0, 50);
+        estimator.observe(1.0, SAMPLE_RATE as u64 + 2400);
+        let estimate = estimator.estimate(1.0);
+        assert!(estimate > SAMPLE_RATE as u64 && estimate < SAMPLE_RATE as u64 + 2400);
+    }
+
+    #[test]
+    fn estimates_never_move_backwards() {
+        let mut estimator = playing();
+        feed(&mut estimator, 0.0, 1.0, 50);
+        let before = estimator.estimate(1.0);
+        estimator.observe(1.0, before - 480);
+        assert!(estimator.estimate(1.0) >= before);
+    }
+
+    #[test]
+    fn follows_playback_rate_outside_default_range() {
+        let mut estimator = playing();
+        estimator.set_playback_rate(0.0, 4.0);
+        feed(&mut estimator, 0.0, 4.0, 200);
+        let expected = 4.0 * 4.0 * SAMPLE_RATE as f64;
+        let error = estimator.estimate(4.0) as f64 - expected;
+        assert!(error.abs() < 48.0, "off by {} frames at 4x", error);
+    }
+}
@@ -0,0 +1,429 @@
+/// Per-bin summary that can be built from samples and combined with its neighbours.
+///
+/// `merge` must be exact and associative so that a coarse bin built from two
+/// finer ones equals a bin built directly from their samples.
+pub trait Bin: Copy + Default {
+    fn add_sample(&mut self, sample: f32);
+    fn merge(self, other: Self) -> Self;
+}
+
+/// Summaries of decoded audio at power-of-two zoom levels.
+///
+/// Level 0 holds one bin per `base_bin_frames` frames; every level above it
+/// holds one bin per two bins of the level below, so level `n` covers
+/// `base_bin_frames << n` frames per bin. Audio is appended in chunks as it
+/// decodes, and queries see everything appended so far, including a trailing
+/// partial bin.
+#[derive(Debug, Clone)]
+pub struct Pyramid<B: Bin> {
+    base_bin_frames: u64,
+    frame_count: u64,
+    // levels[channel][level][bin]
+    levels: Vec<Vec<Vec<B>>>,
+    // Partially filled level-0 bin per channel
+    pending: Vec<B>,
+    pending_frames: u64,
+}
+
+impl<B: Bin> Pyramid<B> {
+    pub fn new(channel_count: usize, base_bin_frames: u64) -> Self {
+        assert!(channel_count > 0, "channel count must be non-zero");
+        assert!(base_bin_frames > 0, "base bin size must be non-zero");
+        Pyramid {
+            base_bin_frames,
+            frame_count: 0,
+            levels: vec![vec![Vec::new()]; channel_count],
+            pending: vec![B::default(); channel_count],
+            pending_frames: 0,
+        }
+    }
+
+    pub fn channel_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn base_bin_frames(&self) -> u64 {
+        self.base_bin_frames
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Frames covered by one bin at `level`.
+    pub fn bin_frames(&self, level: usize) -> u64 {
+        self.base_bin_frames << level
+    }
+
+    /// Completed bins at `level` for `channel`. The trailing partial bin is not included.
+    pub fn level(&self, channel: usize, level: usize) -> &[B] {
+        &self.levels[channel][level]
+    }
+
+    /// Trailing partial level-0 bin of `channel`, covering `pending_frames()` frames.
+    pub fn pending(&self, channel: usize) -> B {
+        self.pending[channel]
+    }
+
+    pub fn pending_frames(&self) -> u64 {
+        self.pending_frames
+    }
+
+    /// Builds a pyramid from precomputed level-0 bins, one vector per channel.
+    pub fn from_base_bins(base_bin_frames: u64, channels: Vec<Vec<B>>) -> Self {
+        assert!(
+            channels
+                .windows(2)
+                .all(|pair| pair[0].len() == pair[1].len()),
+            "channels differ in length"
+        );
+        let mut pyramid = Pyramid::new(channels.len(), base_bin_frames);
+        for (channel, bins) in channels.into_iter().enumerate() {
+            for bin in bins {
+                pyramid.push_bin(channel, bin);
+            }
+        }
+        pyramid.frame_count = pyramid.levels[0][0].len() as u64 * base_bin_frames;
+        pyramid
+    }
+
+    /// Reassembles a pyramid from stored bins, as laid out by `level` and
+    /// `pending`. Returns `None` if the level sizes do not fit together.
+    pub(crate) fn from_parts(
+        base_bin_frames: u64,
+        levels: Vec<Vec<Vec<B>>>,
+        pending: Vec<B>,
+        pending_frames: u64,
+    ) -> Option<Self> {
+        if base_bin_frames == 0 || pending_frames >= base_bin_frames {
+            return None;
+        }
+        if levels.is_empty() || pending.len() != levels.len() {
+            return None;
+        }
+        let level_count = levels[0].len();
+        for channel in &levels {
+            if channel.len() != level_count || level_count == 0 {
+                return None;
+            }
+            for pair in channel.windows(2) {
+                if pair[1].len() != pair[0].len() / 2 {
+                    return None;
+                }
+            }
+            // A level with two bins always has a parent
+            if channel[level_count - 1].len() > 1 {
+                return None;
+            }
+            if channel[0].len() != levels[0][0].len() {
+                return None;
+            }
+        }
+        let frame_count = (levels[0][0].len() as u64)
+            .checked_mul(base_bin_frames)?
+            .checked_add(pending_frames)?;
+        Some(Pyramid {
+            base_bin_frames,
+            frame_count,
+            levels,
+            pending,
+            pending_frames,
+        })
+    }
+
+    /// Coarsest level whose bins are no wider than `frames_per_bin`.
+    pub fn level_for(&self, frames_per_bin: f64) -> usize {
+        let mut level = 0;
+        while level + 1 < self.level_count() && self.bin_frames(level + 1) as f64 <= frames_per_bin
+        {
+            level += 1;
+        }
+        level
+    }
+
+    /// Appends a chunk of deinterleaved samples, one slice per channel.
+    pub fn append<C: AsRef<[f32]>>(&mut self, channels: &[C]) {
+        assert_eq!(
+            channels.len(),
+            self.channel_count(),
+            "chunk channel count does not match pyramid"
+        );
+        let len = channels[0].as_ref().len();
+        assert!(
+            channels.iter().all(|c| c.as_ref().len() == len),
+            "chunk channels differ in length"
+        );
+
+        let mut offset = 0;
+        while offset < len {
+            let take = ((self.base_bin_frames - self.pending_frames) as usize).min(len - offset);
+            for (channel, samples) in channels.iter().enumerate() {
+                let pending = &mut self.pending[channel];
+                for &sample in &samples.as_ref()[offset..offset + take] {
+                    pending.add_sample(sample);
+                }
+            }
+            offset += take;
+            self.pending_frames += take as u64;
+            self.frame_count += take as u64;
+
+            if self.pending_frames == self.base_bin_frames {
+                for channel in 0..self.channel_count() {
+                    let bin = std::mem::take(&mut self.pending[channel]);
+                    self.push_bin(channel, bin);
+                }
+                self.pending_frames = 0;
+            }
+        }
+    }
+
+    fn push_bin(&mut self, channel: usize, bin: B) {
+        let levels = &mut self.levels[channel];
+        let mut level = 0;
+        let mut bin = bin;
+        loop {
+            levels[level].push(bin);
+            let len = levels[level].len();
+            if !len.is_multiple_of(2) {
+                break;
+            }
+            bin = levels[level][len - 2].merge(levels[level][len - 1]);
+            level += 1;
+            if level == levels.len() {
+                levels.push(Vec::new());
+            }
+        }
+    }
+
+    /// Summary of `channel` over frames `start..end`.
+    ///
+    /// Exact at level-0 resolution: a range that does not start or end on a
+    /// `base_bin_frames` boundary is widened outward to the enclosing bins.
+    pub fn summary(&self, channel: usize, start: u64, end: u64) -> B {
+        let end = end.min(self.frame_count);
+        if start >= end {
+            return B::default();
+        }
+
+        let levels = &self.levels[channel];
+        let complete = levels[0].len() as u64;
+        let mut lo = start / self.base_bin_frames;
+        let mut hi = end.div_ceil(self.base_bin_frames);
+
+        let mut acc = B::default();
+        if hi > complete {
+            acc = acc.merge(self.pending[channel]);
+            hi = complete;
+        }
+
+        // Walk up the pyramid taking the unpaired bin at each edge, so every
+        // bin in lo..hi is covered by exactly one stored summary.
+        let mut level = 0;
+        while lo < hi {
+            let bins = &levels[level];
+            if level + 1 == levels.len() {
+                for bin in &bins[lo as usize..hi as usize] {
+                    acc = acc.merge(*bin);
+                }
+                break;
+            }
+            if lo & 1 == 1 {
+                acc = acc.merge(bins[lo as usize]);
+                lo += 1;
+            }
+            if hi & 1 == 1 {
+                hi -= 1;
+                acc = acc.merge(bins[hi as usize]);
+            }
+            lo >>= 1;
+            hi >>= 1;
+            level += 1;
+        }
+        acc
+    }
+
+    /// Splits `start..end` into `width` equal columns and summarizes each.
+    ///
+    /// Column `i` asks for frames `start + (end - start) * i / width` up to the
+    /// next column's start, so every frame is asked for by exactly one column.
+    /// Each column is then widened to whole `base_bin_frames` bins like
+    /// [`summary`](Self::summary), so when a boundary falls inside a bin, both
+    /// neighbouring columns include that bin. A column is exact only when its
+    /// boundaries fall on bin boundaries, and columns narrower than a bin
+    /// repeat the bins around them.
+    pub fn columns(&self, channel: usize, start: u64, end: u64, width: usize) -> Vec<B> {
+        let span = end.saturating_sub(start) as u128;
+        let boundary = |i: usize| start + (span * i as u128 / width as u128) as u64;
+        (0..width)
+            .map(|i| self.summary(channel, boundary(i), boundary(i + 1)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peaks::Peak;
+
+    // xorshift64, so the random cases are the same on every run
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, bound: u64) -> u64 {
+            self.next() % bound
+        }
+
+        fn sample(&mut self) -> f32 {
+            (self.next() >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+        }
+    }
+
+    // Appends `channels` to a new pyramid in chunks of random length
+    fn build(rng: &mut Rng, channels: &[Vec<f32>], base_bin_frames: u64) -> Pyramid<Peak> {
+        let mut pyramid = Pyramid::new(channels.len(), base_bin_frames);
+        let len = channels[0].len();
+        let mut offset = 0;
+        while offset < len {
+            let take = (rng.below(300) as usize + 1).min(len - offset);
+            let chunk: Vec<&[f32]> = channels
+                .iter()
+                .map(|samples| &samples[offset..offset + take])
+                .collect();
+            pyramid.append(&chunk);
+            offset += take;
+        }
+        pyramid
+    }
+
+    fn brute_force(samples: &[f32], start: u64, end: u64) -> Peak {
+        let mut peak = Peak::EMPTY;
+        for &sample in &samples[start as usize..end as usize] {
+            peak.add_sample(sample);
+        }
+        peak
+    }
+
+    #[test]
+    fn summary_matches_brute_force() {
+        let mut rng = Rng(0x9e3779b97f4a7c15);
+        let base = 16;
+        // Not a whole number of bins, so the last one is partial
+        let frames = 5003;
+        let channels: Vec<Vec<f32>> = (0..2)
+            .map(|_| (0..frames).map(|_| rng.sample()).collect())
+            .collect();
+        let pyramid = build(&mut rng, &channels, base);
+        assert_eq!(pyramid.frame_count(), frames as u64);
+        assert_eq!(pyramid.pending_frames(), frames as u64 % base);
+
+        for _ in 0..2000 {
+            let channel = rng.below(2) as usize;
+            let start = rng.below(frames as u64 + 40);
+            let end = start + rng.below(frames as u64 / 2);
+            // The summary covers whole base bins, clipped to the audio
+            let widened_start = start / base * base;
+            let widened_end = (end.div_ceil(base) * base).min(frames as u64);
+            let expected = if start >= end.min(frames as u64) {
+                Peak::EMPTY
+            } else {
+                brute_force(&channels[channel], widened_start, widened_end)
+            };
+            assert_eq!(
+                pyramid.summary(channel, start, end),
+                expected,
+                "channel {} frames {}..{}",
+                channel,
+                start,
+                end
+            );
+        }
+    }
+
+    #[test]
+    fn unaligned_range_widens_to_base_bins() {
+        let mut samples = vec![0.0; 64];
+        samples[3] = 1.0;
+        samples[60] = -1.0;
+        let pyramid = Pyramid::<Peak>::from_base_bins(
+            16,
+            vec![
+                samples
+                    .chunks(16)
+                    .map(|chunk| brute_force(chunk, 0, 16))
+                    .collect(),
+            ],
+        );
+        assert_eq!(pyramid.summary(0, 8, 12), Peak { min: 0.0, max: 1.0 });
+        assert_eq!(pyramid.summary(0, 20, 40), Peak { min: 0.0, max: 0.0 });
+        assert_eq!(
+            pyramid.summary(0, 17, 49),
+            Peak {
+                min: -1.0,
+                max: 0.0
+            }
+        );
+    }
+
+    #[test]
+    fn from_base_bins_matches_append() {
+        let mut rng = Rng(42);
+        let samples: Vec<f32> = (0..4096).map(|_| rng.sample()).collect();
+        let appended = build(&mut rng, std::slice::from_ref(&samples), 32);
+        let bins = samples
+            .chunks(32)
+            .map(|chunk| brute_force(chunk, 0, chunk.len() as u64))
+            .collect();
+        let rebuilt = Pyramid::from_base_bins(32, vec![bins]);
+        assert_eq!(rebuilt.level_count(), appended.level_count());
+        for level in 0..appended.level_count() {
+            assert_eq!(rebuilt.level(0, level), appended.level(0, level));
+        }
+    }
+
+    #[test]
+    fn columns_cover_every_frame_once() {
+        let mut rng = Rng(7);
+        let samples: Vec<f32> = (0..1000).map(|_| rng.sample()).collect();
+        let pyramid = build(&mut rng, std::slice::from_ref(&samples), 1);
+        let columns = pyramid.columns(0, 100, 900, 7);
+        assert_eq!(columns.len(), 7);
+        let merged = columns.into_iter().fold(Peak::EMPTY, Peak::merge);
+        assert_eq!(merged, brute_force(&samples, 100, 900));
+    }
+
+    #[test]
+    fn columns_share_bins_split_by_a_boundary() {
+        let mut samples = vec![0.0; 64];
+        samples[20] = 1.0;
+        samples[27] = -1.0;
+        let pyramid = Pyramid::<Peak>::from_base_bins(
+            16,
+            vec![
+                samples
+                    .chunks(16)
+                    .map(|chunk| brute_force(chunk, 0, 16))
+                    .collect(),
+            ],
+        );
+        // The boundary at frame 24 splits the bin over 16..32, so both
+        // columns see both extremes, though each holds only one of them
+        let columns = pyramid.columns(0, 8, 40, 2);
+        let widened = Peak {
+            min: -1.0,
+            max: 1.0,
+        };
+        assert_eq!(columns, [widened, widened]);
+        assert_eq!(brute_force(&samples, 8, 24), Peak { min: 0.0, max: 1.0 });
+    }
+}
@@ -0,0 +1,270 @@
+use crate::viewport::{Column, Viewport, ViewportError, WaveformFrame};
+
+/// Straight (non-premultiplied) RGBA color.
+pub type Rgba = [u8; 4];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RasterStyle {
+    pub background: Rgba,
+    pub peak: Rgba,
+    pub rms: Rgba,
+    pub played_peak: Rgba,
+    pub played_rms: Rgba,
+    pub playhead: Rgba,
+}
+
+impl Default for RasterStyle {
+    fn default() -> Self {
+        RasterStyle {
+            background: [0, 0, 0, 0],
+            peak: [136, 136, 136, 255],
+            rms: [187, 187, 187, 255],
+            played_peak: [48, 110, 200, 255],
+            played_rms: [110, 160, 230, 255],
+            playhead: [220, 40, 40, 255],
+        }
+    }
+}
+
+/// RGBA8 pixels, row-major from the top-left corner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    fn new(width: u32, height: u32, fill: Rgba) -> Self {
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for _ in 0..width as usize * height as usize {
+            pixels.extend_from_slice(&fill);
+        }
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Rgba {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        [
+            self.pixels[i],
+            self.pixels[i + 1],
+            self.pixels[i + 2],
+            self.pixels[i + 3],
+        ]
+    }
+
+    // Source-over blend of `color` at `coverage` (0..=1) of its own alpha.
+    //
+    // Only integer math touches the stored pixels, and coverage comes from
+    // single f32 additions, subtractions and roundings, which IEEE 754 fixes
+    // exactly on every target, so native and wasm builds produce identical output.
+    fn blend(&mut self, x: u32, y: u32, color: Rgba, coverage: f32) {
+        let alpha = (coverage.clamp(0.0, 1.0) * color[3] as f32).round() as u32;
+        if alpha == 0 {
+            return;
+        }
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        let dst = &mut self.pixels[i..i + 4];
+        let dst_alpha = dst[3] as u32;
+        let out_alpha = alpha * 255 + dst_alpha * (255 - alpha);
+        if out_alpha == 0 {
+            return;
+        }
+        for c in 0..3 {
+            let src = color[c] as u32 * alpha * 255;
+            let under = dst[c] as u32 * dst_alpha * (255 - alpha);
+            dst[c] = ((src + under + out_alpha / 2) / out_alpha) as u8;
+        }
+        dst[3] = ((out_alpha + 127) / 255) as u8;
+    }
+
+    // Fills rows `top..bottom` (fractional) of column `x`, blending partially
+    // covered edge rows by their coverage.
+    fn fill_span(&mut self, x: u32, top: f32, bottom: f32, color: Rgba) {
+        let (top, bottom) = min_thickness(top, bottom);
+        let top = top.max(0.0);
+        let bottom = bottom.min(self.height as f32);
+        if top >= bottom {
+            return;
+        }
+        let first = top.floor() as u32;
+        let last = (bottom.ceil() as u32).min(self.height);
+        for y in first..last {
+            let row_top = y as f32;
+            let coverage = bottom.min(row_top + 1.0) - top.max(row_top);
+            self.blend(x, y, color, coverage);
+        }
+    }
+}
+
+// Silence still draws a one-pixel line rather than vanishing.
+fn min_thickness(top: f32, bottom: f32) -> (f32, f32) {
+    if bottom - top >= 1.0 {
+        return (top, bottom);
+    }
+    let middle = (top + bottom) / 2.0;
+    (middle - 0.5, middle + 0.5)
+}
+
+impl WaveformFrame {
+    /// Rasterizes the frame; columns starting before `played_until` use the played colors.
+    pub fn rasterize(&self, style: &RasterStyle, played_until: Option<u64>) -> Image {
+        let mut image = Image::new(self.width, self.height, style.background);
+        let played = |column: &Column| played_until.is_some_and(|frame| column.start_frame < frame);
+
+        for lane in &self.lanes {
+            for (x, column) in lane.columns.iter().enumerate() {
+                if column.is_empty() {
+                    continue;
+                }
+                let (peak, rms) = if played(column) {
+                    (style.played_peak, style.played_rms)
+                } else {
+                    (style.peak, style.rms)
+                };
+                image.fill_span(x as u32, column.peak_top, column.peak_bottom, peak);
+                if column.rms_bottom > column.rms_top {
+                    image.fill_span(x as u32, column.rms_top, column.rms_bottom, rms);
+                }
+            }
+        }
+        image
+    }
+}
+
+impl Viewport<'_> {
+    /// Renders and rasterizes in one step, drawing a playhead line at `playhead` if visible.
+    pub fn rasterize(
+        &self,
+        width_px: u32,
+        style: &RasterStyle,
+        playhead: Option<u64>,
+    ) -> Result<Image, ViewportError> {
+        let mut image = self.render(width_px)?.rasterize(style, playhead);
+        if let Some(frame) = playhead {
+            let x = self.frame_to_x(frame, width_px).floor();
+            if x >= 0.0 && x < width_px as f64 {
+                for y in 0..image.height {
+                    image.blend(x as u32, y, style.playhead, 1.0);
+                }
+            }
+        }
+        Ok(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peaks::PeakPyramid;
+    use crate::viewport::Lane;
+
+    const BLACK: Rgba = [0, 0, 0, 255];
+
+    fn style() -> RasterStyle {
+        RasterStyle {
+            background: BLACK,
+            ..RasterStyle::default()
+        }
+    }
+
+    fn column(start_frame: u64, peak: (f32, f32), rms: (f32, f32)) -> Column {
+        Column {
+            start_frame,
+            end_frame: start_frame + 1,
+            peak_top: peak.0,
+            peak_bottom: peak.1,
+            rms_top: rms.0,
+            rms_bottom: rms.1,
+        }
+    }
+
+    // Three columns over ten rows: whole-pixel edges, a half-covered top
+    // edge, and silence
+    fn frame() -> WaveformFrame {
+        WaveformFrame {
+            width: 3,
+            height: 10,
+            lanes: vec![Lane {
+                top: 0.0,
+                height: 10.0,
+                columns: vec![
+                    column(0, (2.0, 8.0), (4.0, 6.0)),
+                    column(1, (2.5, 8.0), (5.0, 5.0)),
+                    column(2, (5.0, 5.0), (5.0, 5.0)),
+                ],
+            }],
+        }
+    }
+
+    #[test]
+    fn fills_background_peaks_and_rms() {
+        let style = style();
+        let image = frame().rasterize(&style, None);
+        assert_eq!(
+            (image.width, image.height, image.pixels.len()),
+            (3, 10, 120)
+        );
+        let column: Vec<Rgba> = (0..10).map(|y| image.pixel(0, y)).collect();
+        let (bg, peak, rms) = (BLACK, style.peak, style.rms);
+        assert_eq!(column, [bg, bg, peak, peak, rms, rms, peak, peak, bg, bg]);
+    }
+
+    #[test]
+    fn blends_edges_by_coverage() {
+        let image = frame().rasterize(&style(), None);
+        // Half of 255 alpha rounds to 128; 136 * 128 / 255 rounds to 68
+        assert_eq!(image.pixel(1, 1), BLACK);
+        assert_eq!(image.pixel(1, 2), [68, 68, 68, 255]);
+        assert_eq!(image.pixel(1, 3), style().peak);
+
+        // Silence is a one-pixel line split across the rows either side of it
+        assert_eq!(image.pixel(2, 4), [68, 68, 68, 255]);
+        assert_eq!(image.pixel(2, 5), [68, 68, 68, 255]);
+        assert_eq!(image.pixel(2, 6), BLACK);
+
+        // Over a transparent background the color is kept and alpha carries coverage
+        let transparent = RasterStyle::default();
+        let image = frame().rasterize(&transparent, None);
+        assert_eq!(image.pixel(1, 2), [136, 136, 136, 128]);
+        assert_eq!(image.pixel(0, 0), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn played_columns_use_played_colors() {
+        let style = style();
+        let image = frame().rasterize(&style, Some(1));
+        assert_eq!(image.pixel(0, 2), style.played_peak);
+        assert_eq!(image.pixel(0, 4), style.played_rms);
+        assert_eq!(image.pixel(1, 3), style.peak);
+    }
+
+    #[test]
+    fn draws_playhead_column() {
+        let channel: Vec<f32> = (0..40)
+            .map(|i| if i % 2 == 0 { 0.5 } else { -0.5 })
+            .collect();
+        let mut peaks = PeakPyramid::new(1, 1);
+        peaks.append(&[channel]);
+        let style = style();
+        let image = Viewport::new(&peaks, 10)
+            .rasterize(4, &style, Some(25))
+            .unwrap();
+        for y in 0..10 {
+            assert_eq!(image.pixel(2, y), style.playhead);
+        }
+        assert_eq!(image.pixel(1, 5), style.played_peak);
+        assert_eq!(image.pixel(3, 5), style.peak);
+        assert_eq!(image.pixel(3, 0), BLACK);
+
+        // Off-screen playheads draw nothing
+        let image = Viewport::new(&peaks, 10)
+            .rasterize(4, &style, Some(40))
+            .unwrap();
+        assert_eq!(image.pixel(3, 0), BLACK);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::pyramid::{Bin, Pyramid};
+
+/// Sum of squares and sample count over a run of frames.
+///
+/// Kept as raw sums rather than a finished RMS value so that two bins merge
+/// exactly into their parent.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rms {
+    pub sum_squares: f64,
+    pub count: u64,
+}
+
+impl Rms {
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn value(&self) -> f32 {
+        if self.count == 0 {
+            0.0
+        } else {
+            (self.sum_squares / self.count as f64).sqrt() as f32
+        }
+    }
+}
+
+impl Bin for Rms {
+    fn add_sample(&mut self, sample: f32) {
+        if !sample.is_nan() {
+            self.sum_squares += sample as f64 * sample as f64;
+            self.count += 1;
+        }
+    }
+
+    fn merge(self, other: Rms) -> Rms {
+        Rms {
+            sum_squares: self.sum_squares + other.sum_squares,
+            count: self.count + other.count,
+        }
+    }
+}
+
+/// RMS pyramid; see [`Pyramid`].
+///
+/// Built with the same `base_bin_frames` as a [`PeakPyramid`](crate::PeakPyramid),
+/// its bins line up one-to-one with the peak bins at every level.
+pub type RmsEnvelope = Pyramid<Rms>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::peaks::PeakPyramid;
+
+    fn direct_rms(samples: &[f32]) -> f32 {
+        let sum_squares: f64 = samples.iter().map(|&s| s as f64 * s as f64).sum();
+        (sum_squares / samples.len() as f64).sqrt() as f32
+    }
+
+    // A decaying sine, 1000 frames in bins of 64, so the last bin holds 40
+    fn samples() -> Vec<f32> {
+        (0..1000)
+            .map(|i| (i as f32 * 0.05).sin() * (1.0 - i as f32 / 1200.0))
+            .collect()
+    }
+
+    #[test]
+    fn merged_bins_match_direct_rms() {
+        let samples = samples();
+        let mut envelope = RmsEnvelope::new(1, 64);
+        for chunk in samples.chunks(100) {
+            envelope.append(&[chunk]);
+        }
+        assert_eq!(envelope.pending_frames(), 40);
+
+        for (start, end) in [(0, 1000), (0, 64), (128, 512), (960, 1000), (192, 1000)] {
+            let merged = envelope.summary(0, start, end);
+            assert_eq!(merged.count, end - start);
+            let expected = direct_rms(&samples[start as usize..end as usize]);
+            assert!(
+                (merged.value() - expected).abs() < 1e-6,
+                "frames {}..{}: {} vs {}",
+                start,
+                end,
+                merged.value(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn bins_line_up_with_peaks() {
+        let samples = samples();
+        let mut envelope = RmsEnvelope::new(1, 64);
+        let mut peaks = PeakPyramid::new(1, 64);
+        envelope.append(&[&samples]);
+        peaks.append(&[&samples]);
+        assert_eq!(envelope.level_count(), peaks.level_count());
+        for level in 0..peaks.level_count() {
+            let bins = envelope.level(0, level);
+            assert_eq!(bins.len(), peaks.level(0, level).len());
+            assert!(
+                bins.iter()
+                    .all(|bin| bin.count == envelope.bin_frames(level))
+            );
+        }
+    }
+
+    #[test]
+    fn nan_samples_are_skipped() {
+        let mut bin = Rms::default();
+        for sample in [0.5, f32::NAN, -0.5] {
+            bin.add_sample(sample);
+        }
+        assert_eq!(bin.count, 2);
+        assert_eq!(bin.value(), 0.5);
+    }
+}
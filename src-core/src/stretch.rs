@@ -0,0 +1,304 @@
+use std::f32::consts::PI;
+
+// Coarse search stride and correlation decimation; refined around the coarse best
+const SEARCH_STEP: usize = 4;
+const CORRELATION_STEP: usize = 4;
+
+/// Time-stretches audio by WSOLA so it plays `rate` times faster at the same pitch.
+///
+/// Takes and returns one buffer per channel; the output holds about
+/// `len / rate` frames, and output frame `f` corresponds to input frame
+/// `f * rate`. Every channel is cut at the same points, chosen on a mixdown,
+/// so inter-channel phase is preserved.
+pub fn time_stretch<C: AsRef<[f32]>>(channels: &[C], sample_rate: u32, rate: f64) -> Vec<Vec<f32>> {
+    assert!(rate > 0.0 && rate.is_finite(), "rate must be positive");
+    let input_len = channels.first().map_or(0, |channel| channel.as_ref().len());
+    if rate == 1.0 || input_len == 0 {
+        return channels
+            .iter()
+            .map(|channel| channel.as_ref().to_vec())
+            .collect();
+    }
+    let output_len = (input_len as f64 / rate).round() as usize;
+    Stretch::new(channels.len(), sample_rate, rate, 0).render(channels, 0, output_len)
+}
+
+/// [`time_stretch`] a block at a time, from any frame of a longer source.
+///
+/// Each `render` continues the same run where the last one stopped, so
+/// consecutive blocks join seamlessly and only the audio around them is read.
+pub struct Stretch {
+    rate: f64,
+    hop: usize,
+    tolerance: usize,
+    hann: Vec<f32>,
+    // Source frame at output frame 0
+    start: u64,
+    // Output frames rendered so far
+    produced: u64,
+    // Output frame of the next window, and source frame of the last one placed
+    next_window: u64,
+    previous: Option<i64>,
+    // Overlap-added output from `produced` on, one per channel
+    pending: Vec<Vec<f32>>,
+}
+
+impl Stretch {
+    pub fn new(channel_count: usize, sample_rate: u32, rate: f64, start_frame: u64) -> Self {
+        assert!(rate > 0.0 && rate.is_finite(), "rate must be positive");
+        // About 30 ms windows with 50% overlap; periodic Hann sums to one
+        let window = ((sample_rate as usize * 3 / 100) & !1).max(64);
+        let hop = window / 2;
+        Stretch {
+            rate,
+            hop,
+            tolerance: hop / 2,
+            hann: (0..window)
+                .map(|n| 0.5 - 0.5 * (2.0 * PI * n as f32 / window as f32).cos())
+                .collect(),
+            start: start_frame,
+            produced: 0,
+            next_window: 0,
+            previous: None,
+            pending: vec![Vec::new(); channel_count],
+        }
+    }
+
+    /// Source frame the next rendered frame corresponds to.
+    pub fn source_position(&self) -> u64 {
+        self.start + (self.produced as f64 * self.rate).round() as u64
+    }
+
+    /// Output frames that bring the source position up to `source_frame`.
+    pub fn frames_until(&self, source_frame: u64) -> usize {
+        let end = (source_frame.saturating_sub(self.start) as f64 / self.rate).round() as u64;
+        end.saturating_sub(self.produced) as usize
+    }
+
+    /// Source frames `start..end` that rendering `frames` more frames reads.
+    pub fn input_range(&self, frames: usize) -> (u64, u64) {
+        let target = self.produced + frames as u64;
+        if self.next_window >= target {
+            return (self.start, self.start);
+        }
+        let last_window =
+            self.next_window + (target - 1 - self.next_window) / self.hop as u64 * self.hop as u64;
+        let nominal = |window: u64| self.start as i64 + (window as f64 * self.rate).round() as i64;
+        // Candidates lie within the search radius of their nominal start, and
+        // each is matched against what followed the window before it
+        let reach = (self.tolerance + SEARCH_STEP) as i64;
+        let mut start = nominal(self.next_window) - reach;
+        let mut end = nominal(last_window) + reach + self.hann.len() as i64;
+        if let Some(previous) = self.previous {
+            start = start.min(previous + self.hop as i64);
+            end = end.max(previous + 2 * self.hop as i64);
+        }
+        (start.max(0) as u64, end.max(0) as u64)
+    }
+
+    /// Renders the next `frames` frames, one buffer per channel. `input` holds
+    /// source frames from `input_start` on and should cover
+    /// `input_range(frames)`; any frame it lacks is taken as silence.
+    pub fn render<C: AsRef<[f32]>>(
+        &mut self,
+        input: &[C],
+        input_start: u64,
+        frames: usize,
+    ) -> Vec<Vec<f32>> {
+        assert_eq!(input.len(), self.pending.len(), "channel count changed");
+        let origin = input_start as i64;
+        let input_len = input.first().map_or(0, |channel| channel.as_ref().len());
+        let scale = 1.0 / input.len() as f32;
+        let mut guide = vec![0.0f32; input_len];
+        for channel in input {
+            for (mixed, &sample) in guide.iter_mut().zip(channel.as_ref()) {
+                *mixed += sample * scale;
+            }
+        }
+
+        let target = self.produced + frames as u64;
+        let window = self.hann.len();
+        while self.next_window < target {
+            let nominal = self.start as i64 + (self.next_window as f64 * self.rate).round() as i64;
+            let position = match self.previous {
+                None => self.start as i64,
+                // Match what would naturally have followed the previous window
+                Some(previous) => {
+                    origin
+                        + best_offset(
+                            &guide,
+                            (previous - origin) as isize + self.hop as isize,
+                            (nominal - origin) as isize,
+                            self.tolerance,
+                            self.hop,
+                        ) as i64
+                }
+            };
+            let offset = (self.next_window - self.produced) as usize;
+            for (out, input) in self.pending.iter_mut().zip(input) {
+                if out.len() < offset + window {
+                    out.resize(offset + window, 0.0);
+                }
+                let input = input.as_ref();
+                for (n, &weight) in self.hann.iter().enumerate() {
+                    out[offset + n] +=
+                        weight * sample(input, (position - origin) as isize + n as isize);
+                }
+            }
+            self.previous = Some(position);
+            self.next_window += self.hop as u64;
+        }
+
+        self.produced = target;
+        self.pending
+            .iter_mut()
+            .map(|pending| {
+                if pending.len() < frames {
+                    pending.resize(frames, 0.0);
+                }
+                let rest = pending.split_off(frames);
+                std::mem::replace(pending, rest)
+            })
+            .collect()
+    }
+}
+
+// Start near `nominal` whose first `length` frames best correlate with those at `target`
+fn best_offset(
+    guide: &[f32],
+    target: isize,
+    nominal: isize,
+    tolerance: usize,
+    length: usize,
+) -> isize {
+    let tolerance = tolerance as isize;
+    let correlate = |candidate: isize| -> f32 {
+        (0..length)
+            .step_by(CORRELATION_STEP)
+            .map(|n| sample(guide, target + n as isize) * sample(guide, candidate + n as isize))
+            .sum()
+    };
+
+    let mut best = nominal;
+    let mut best_score = f32::NEG_INFINITY;
+    for offset in (-tolerance..=tolerance).step_by(SEARCH_STEP) {
+        let score = correlate(nominal + offset);
+        if score > best_score {
+            best = nominal + offset;
+            best_score = score;
+        }
+    }
+    let coarse = best;
+    let refine = SEARCH_STEP as isize - 1;
+    for candidate in coarse - refine..=coarse + refine {
+        let score = correlate(candidate);
+        if score > best_score {
+            best = candidate;
+            best_score = score;
+        }
+    }
+    best
+}
+
+fn sample(input: &[f32], at: isize) -> f32 {
+    if at < 0 {
+        return 0.0;
+    }
+    input.get(at as usize).copied().unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(frames: usize, sample_rate: u32) -> Vec<Vec<f32>> {
+        let left = (0..frames)
+            .map(|i| (i as f32 * 440.0 * 2.0 * PI / sample_rate as f32).sin() * 0.5)
+            .collect();
+        let right = (0..frames)
+            .map(|i| (i as f32 * 660.0 * 2.0 * PI / sample_rate as f32).sin() * 0.25)
+            .collect();
+        vec![left, right]
+    }
+
+    #[test]
+    fn output_length_follows_rate() {
+        let input = tone(8000, 8000);
+        for rate in [0.5, 0.75, 1.0, 1.5, 2.0] {
+            let output = time_stretch(&input, 8000, rate);
+            assert_eq!(output.len(), 2);
+            assert_eq!(output[0].len(), (8000.0 / rate).round() as usize);
+        }
+    }
+
+    #[test]
+    fn blocks_match_one_pass() {
+        let input = tone(12000, 8000);
+        for rate in [0.6, 1.7] {
+            let whole = time_stretch(&input, 8000, rate);
+            let mut stretch = Stretch::new(2, 8000, rate, 0);
+            let mut blocks = vec![Vec::new(); 2];
+            let mut block = 0;
+            while blocks[0].len() < whole[0].len() {
+                // Uneven blocks, each given only the source they need
+                let frames = (300 + block * 217).min(whole[0].len() - blocks[0].len());
+                let (start, end) = stretch.input_range(frames);
+                let end = end.min(input[0].len() as u64);
+                let slices: Vec<&[f32]> = input
+                    .iter()
+                    .map(|channel| &channel[start as usize..end as usize])
+                    .collect();
+                for (all, output) in blocks
+                    .iter_mut()
+                    .zip(stretch.render(&slices, start, frames))
+                {
+                    all.extend(output);
+                }
+                block += 1;
+            }
+            assert_eq!(blocks, whole, "rate {}", rate);
+        }
+    }
+
+    #[test]
+    fn starts_mid_source() {
+        let input = tone(12000, 8000);
+        let mut stretch = Stretch::new(2, 8000, 1.25, 4000);
+        assert_eq!(stretch.frames_until(9000), 4000);
+        let output = stretch.render(&input, 0, 4000);
+        assert_eq!(stretch.source_position(), 9000);
+
+        // Past the fade-in the level matches the source's
+        let level =
+            |samples: &[f32]| samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32;
+        let expected = level(&input[0][4000..9000]);
+        assert!((level(&output[0][1000..]) - expected).abs() < expected * 0.1);
+    }
+
+    #[test]
+    fn preserves_pitch() {
+        // Sign changes per second, twice the frequency of a pure tone
+        let crossings_per_second = |samples: &[f32]| {
+            let crossings = samples
+                .windows(2)
+                .filter(|pair| (pair[0] < 0.0) != (pair[1] < 0.0))
+                .count();
+            crossings as f64 * 8000.0 / samples.len() as f64
+        };
+        let input = tone(16000, 8000);
+        let expected = crossings_per_second(&input[0]);
+        for rate in [0.5, 1.5] {
+            let output = time_stretch(&input, 8000, rate);
+            // Away from the fades at either end
+            let middle = &output[0][1000..output[0].len() - 1000];
+            let crossings = crossings_per_second(middle);
+            assert!(
+                (crossings - expected).abs() < expected * 0.02,
+                "rate {}: {} crossings per second, expected {}",
+                rate,
+                crossings,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,476 @@
+use crate::peaks::{Peak, PeakPyramid};
+use crate::pyramid::Bin;
+use crate::rms::{Rms, RmsEnvelope};
+
+use std::fmt;
+
+/// Horizontal zoom of a viewport.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zoom {
+    /// Fixed number of frames per pixel column; the visible span grows with the width.
+    FramesPerPixel(f64),
+    /// Fixed number of frames spread across whatever width is rendered.
+    Span(u64),
+}
+
+/// How channels are arranged vertically.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelLayout {
+    /// One lane per channel, stacked top to bottom.
+    Stacked,
+    /// All channels combined into a single lane.
+    Mixed,
+    /// A single channel filling the whole height.
+    Single(usize),
+}
+
+/// Mapping from sample amplitude to lane height.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AmplitudeScale {
+    /// Amplitude multiplied by `gain`; full scale reaches the lane edge.
+    Linear { gain: f32 },
+    /// Logarithmic; `floor_db` (negative) sits at the lane center, 0 dBFS at the edge.
+    Decibel { floor_db: f32 },
+}
+
+impl AmplitudeScale {
+    /// Maps an amplitude to `-1.0..=1.0`, preserving sign.
+    pub fn apply(&self, amplitude: f32) -> f32 {
+        match *self {
+            AmplitudeScale::Linear { gain } => (amplitude * gain).clamp(-1.0, 1.0),
+            AmplitudeScale::Decibel { floor_db } => {
+                let magnitude = amplitude.abs();
+                if magnitude == 0.0 || floor_db >= 0.0 {
+                    return 0.0;
+                }
+                let db = 20.0 * magnitude.log10();
+                let scaled = ((db - floor_db) / -floor_db).clamp(0.0, 1.0);
+                scaled.copysign(amplitude)
+            }
+        }
+    }
+}
+
+/// A window onto analysed audio that renders to pixel columns on demand.
+///
+/// Rendering is a pure function of the viewport fields and the analysis it
+/// borrows, so the same viewport always produces identical columns.
+#[derive(Debug, Clone, Copy)]
+pub struct Viewport<'a> {
+    pub peaks: &'a PeakPyramid,
+    pub rms: Option<&'a RmsEnvelope>,
+    pub start_frame: u64,
+    pub zoom: Zoom,
+    pub height_px: u32,
+    pub layout: ChannelLayout,
+    pub scale: AmplitudeScale,
+}
+
+/// Why a viewport cannot be rendered.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ViewportError {
+    /// `ChannelLayout::Single` names a channel the peaks do not have.
+    ChannelOutOfRange {
+        channel: usize,
+        channel_count: usize,
+    },
+    /// The RMS envelope was built for a different number of channels than the peaks.
+    RmsChannelMismatch { peaks: usize, rms: usize },
+    /// `Zoom::FramesPerPixel` is not a positive, finite number.
+    InvalidZoom(f64),
+    /// A linear gain that is negative or not finite, or a decibel floor that is not finite.
+    InvalidScale(AmplitudeScale),
+}
+
+impl fmt::Display for ViewportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViewportError::ChannelOutOfRange {
+                channel,
+                channel_count,
+            } => write!(
+                f,
+                "Channel {} is out of range for {} channels",
+                channel, channel_count
+            ),
+            ViewportError::RmsChannelMismatch { peaks, rms } => write!(
+                f,
+                "RMS envelope has {} channels but the peaks have {}",
+                rms, peaks
+            ),
+            ViewportError::InvalidZoom(frames_per_pixel) => {
+                write!(f, "Invalid zoom of {} frames per pixel", frames_per_pixel)
+            }
+            ViewportError::InvalidScale(scale) => write!(f, "Invalid amplitude scale {:?}", scale),
+        }
+    }
+}
+
+impl std::error::Error for ViewportError {}
+
+/// One rendered tick of a viewport.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaveformFrame {
+    pub width: u32,
+    pub height: u32,
+    pub lanes: Vec<Lane>,
+}
+
+/// A horizontal band of the frame showing one channel (or the mix).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lane {
+    pub top: f32,
+    pub height: f32,
+    pub columns: Vec<Column>,
+}
+
+/// One pixel column, with vertical extents in pixels from the top of the frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Column {
+    /// Frames summarized by this column, clipped to the available audio.
+    pub start_frame: u64,
+    pub end_frame: u64,
+    pub peak_top: f32,
+    pub peak_bottom: f32,
+    pub rms_top: f32,
+    pub rms_bottom: f32,
+}
+
+impl Column {
+    /// True when the column lies outside the analysed audio.
+    pub fn is_empty(&self) -> bool {
+        self.start_frame >= self.end_frame
+    }
+}
+
+impl<'a> Viewport<'a> {
+    /// Viewport showing all of `peaks`, one lane per channel, at linear scale.
+    pub fn new(peaks: &'a PeakPyramid, height_px: u32) -> Self {
+        Viewport {
+            peaks,
+            rms: None,
+            start_frame: 0,
+            zoom: Zoom::Span(peaks.frame_count()),
+            height_px,
+            layout: ChannelLayout::Stacked,
+            scale: AmplitudeScale::Linear { gain: 1.0 },
+        }
+    }
+
+    pub fn with_rms(mut self, rms: &'a RmsEnvelope) -> Self {
+        self.rms = Some(rms);
+        self
+    }
+
+    /// First frame of column `column` when rendered `width_px` wide.
+    ///
+    /// `column == width_px` gives the end of the visible span.
+    pub fn column_start(&self, column: u32, width_px: u32) -> u64 {
+        match self.zoom {
+            Zoom::FramesPerPixel(frames_per_pixel) => {
+                self.start_frame + (column as f64 * frames_per_pixel).floor() as u64
+            }
+            Zoom::Span(span) => {
+                if width_px == 0 {
+                    return self.start_frame;
+                }
+                self.start_frame + (span as u128 * column as u128 / width_px as u128) as u64
+            }
+        }
+    }
+
+    /// Horizontal pixel position of `frame`; may fall outside `0..width_px`.
+    pub fn frame_to_x(&self, frame: u64, width_px: u32) -> f64 {
+        let offset = frame as f64 - self.start_frame as f64;
+        match self.zoom {
+            Zoom::FramesPerPixel(frames_per_pixel) => offset / frames_per_pixel,
+            Zoom::Span(span) => offset * width_px as f64 / span as f64,
+        }
+    }
+
+    /// Frame under horizontal pixel position `x`. A zero-width span shows
+    /// only its first frame.
+    pub fn x_to_frame(&self, x: f64, width_px: u32) -> u64 {
+        let offset = match self.zoom {
+            Zoom::FramesPerPixel(frames_per_pixel) => x * frames_per_pixel,
+            Zoom::Span(_) if width_px == 0 => 0.0,
+            Zoom::Span(span) => x * span as f64 / width_px as f64,
+        };
+        (self.start_frame as f64 + offset).max(0.0) as u64
+    }
+
+    /// Renders `width_px` columns. Fails if the layout or RMS envelope does
+    /// not fit the peaks' channels, or the zoom or scale is out of range.
+    pub fn render(&self, width_px: u32) -> Result<WaveformFrame, ViewportError> {
+        if let Zoom::FramesPerPixel(frames_per_pixel) = self.zoom
+            && !(frames_per_pixel > 0.0 && frames_per_pixel.is_finite())
+        {
+            return Err(ViewportError::InvalidZoom(frames_per_pixel));
+        }
+        let valid_scale = match self.scale {
+            AmplitudeScale::Linear { gain } => gain >= 0.0 && gain.is_finite(),
+            AmplitudeScale::Decibel { floor_db } => floor_db.is_finite(),
+        };
+        if !valid_scale {
+            return Err(ViewportError::InvalidScale(self.scale));
+        }
+        let channel_count = self.peaks.channel_count();
+        if let ChannelLayout::Single(channel) = self.layout
+            && channel >= channel_count
+        {
+            return Err(ViewportError::ChannelOutOfRange {
+                channel,
+                channel_count,
+            });
+        }
+        if let Some(rms) = self.rms
+            && rms.channel_count() != channel_count
+        {
+            return Err(ViewportError::RmsChannelMismatch {
+                peaks: channel_count,
+                rms: rms.channel_count(),
+            });
+        }
+
+        let channels: Vec<Vec<usize>> = match self.layout {
+            ChannelLayout::Stacked => (0..channel_count).map(|c| vec![c]).collect(),
+            ChannelLayout::Mixed => vec![(0..channel_count).collect()],
+            ChannelLayout::Single(channel) => vec![vec![channel]],
+        };
+
+        let frame_count = self.peaks.frame_count();
+        let ranges: Vec<(u64, u64)> = (0..width_px)
+            .map(|x| {
+                let start = self.column_start(x, width_px);
+                // Zoomed in past one frame per pixel, show the frame under the column
+                let end = self.column_start(x + 1, width_px).max(start + 1);
+                (start.min(frame_count), end.min(frame_count))
+            })
+            .collect();
+
+        let lane_height = self.height_px as f32 / channels.len() as f32;
+        let lanes = channels
+            .iter()
+            .enumerate()
+            .map(|(index, lane_channels)| {
+                let top = index as f32 * lane_height;
+                let columns = ranges
+                    .iter()
+                    .map(|&(start, end)| self.column(lane_channels, start, end, top, lane_height))
+                    .collect();
+                Lane {
+                    top,
+                    height: lane_height,
+                    columns,
+                }
+            })
+            .collect();
+
+        Ok(WaveformFrame {
+            width: width_px,
+            height: self.height_px,
+            lanes,
+        })
+    }
+
+    fn column(&self, channels: &[usize], start: u64, end: u64, top: f32, height: f32) -> Column {
+        let center = top + height / 2.0;
+        let half = height / 2.0;
+        let y = |amplitude: f32| center - self.scale.apply(amplitude) * half;
+
+        let mut peak = Peak::EMPTY;
+        let mut rms = Rms::default();
+        if start < end {
+            for &channel in channels {
+                peak = peak.merge(self.peaks.summary(channel, start, end));
+                if let Some(envelope) = self.rms {
+                    rms = rms.merge(envelope.summary(channel, start, end));
+                }
+            }
+        }
+
+        if peak.is_empty() {
+            return Column {
+                start_frame: start,
+                end_frame: start,
+                peak_top: center,
+                peak_bottom: center,
+                rms_top: center,
+                rms_bottom: center,
+            };
+        }
+
+        let peak_top = y(peak.max);
+        let peak_bottom = y(peak.min);
+        let level = rms.value();
+        Column {
+            start_frame: start,
+            end_frame: end,
+            peak_top,
+            peak_bottom,
+            // min/max rather than clamp, which panics on NaN bins from a corrupt file
+            rms_top: y(level).max(peak_top).min(peak_bottom),
+            rms_bottom: y(-level).max(peak_top).min(peak_bottom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two channels of a second at 1 kHz: a sine on the left, half-scale DC on the right
+    fn analysis() -> (PeakPyramid, RmsEnvelope) {
+        let left: Vec<f32> = (0..1000).map(|i| (i as f32 * 0.1).sin()).collect();
+        let right = vec![0.5; 1000];
+        let mut peaks = PeakPyramid::new(2, 4);
+        let mut rms = RmsEnvelope::new(2, 4);
+        peaks.append(&[&left, &right]);
+        rms.append(&[&left, &right]);
+        (peaks, rms)
+    }
+
+    #[test]
+    fn render_is_deterministic() {
+        let (peaks, rms) = analysis();
+        let mut viewport = Viewport::new(&peaks, 100).with_rms(&rms);
+        viewport.start_frame = 123;
+        viewport.zoom = Zoom::FramesPerPixel(3.5);
+        let frame = viewport.render(200).unwrap();
+        assert_eq!(frame, viewport.render(200).unwrap());
+    }
+
+    #[test]
+    fn stacked_lanes_split_the_height() {
+        let (peaks, rms) = analysis();
+        let frame = Viewport::new(&peaks, 100)
+            .with_rms(&rms)
+            .render(10)
+            .unwrap();
+        assert_eq!(frame.lanes.len(), 2);
+        assert_eq!((frame.lanes[1].top, frame.lanes[1].height), (50.0, 50.0));
+
+        // The DC lane sits a quarter of its height above its center, RMS included
+        let column = frame.lanes[1].columns[3];
+        assert_eq!((column.start_frame, column.end_frame), (300, 400));
+        assert_eq!((column.peak_top, column.peak_bottom), (62.5, 62.5));
+        assert_eq!((column.rms_top, column.rms_bottom), (62.5, 62.5));
+    }
+
+    #[test]
+    fn single_layout_shows_one_channel() {
+        let (peaks, _) = analysis();
+        let mut viewport = Viewport::new(&peaks, 40);
+        viewport.layout = ChannelLayout::Single(1);
+        let frame = viewport.render(5).unwrap();
+        assert_eq!(frame.lanes.len(), 1);
+        assert_eq!(frame.lanes[0].height, 40.0);
+        assert!(frame.lanes[0].columns.iter().all(|c| c.peak_top == 10.0));
+    }
+
+    #[test]
+    fn rejects_single_channel_out_of_range() {
+        let (peaks, _) = analysis();
+        let mut viewport = Viewport::new(&peaks, 40);
+        viewport.layout = ChannelLayout::Single(2);
+        assert_eq!(
+            viewport.render(5),
+            Err(ViewportError::ChannelOutOfRange {
+                channel: 2,
+                channel_count: 2
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_rms_with_other_channel_count() {
+        let (peaks, _) = analysis();
+        let rms = RmsEnvelope::new(1, 4);
+        let viewport = Viewport::new(&peaks, 40).with_rms(&rms);
+        assert_eq!(
+            viewport.render(5),
+            Err(ViewportError::RmsChannelMismatch { peaks: 2, rms: 1 })
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_zoom() {
+        let (peaks, _) = analysis();
+        let mut viewport = Viewport::new(&peaks, 40);
+        for frames_per_pixel in [0.0, -2.0, f64::NAN, f64::INFINITY] {
+            viewport.zoom = Zoom::FramesPerPixel(frames_per_pixel);
+            assert!(matches!(
+                viewport.render(5),
+                Err(ViewportError::InvalidZoom(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_gain() {
+        let (peaks, rms) = analysis();
+        let mut viewport = Viewport::new(&peaks, 40).with_rms(&rms);
+        for gain in [-1.0, f32::NAN, f32::INFINITY] {
+            viewport.scale = AmplitudeScale::Linear { gain };
+            assert!(matches!(
+                viewport.render(5),
+                Err(ViewportError::InvalidScale(_))
+            ));
+        }
+        viewport.scale = AmplitudeScale::Linear { gain: 0.0 };
+        assert!(viewport.render(5).is_ok());
+    }
+
+    #[test]
+    fn rejects_invalid_floor() {
+        let (peaks, rms) = analysis();
+        let mut viewport = Viewport::new(&peaks, 40).with_rms(&rms);
+        for floor_db in [f32::NAN, f32::NEG_INFINITY] {
+            viewport.scale = AmplitudeScale::Decibel { floor_db };
+            assert!(matches!(
+                viewport.render(5),
+                Err(ViewportError::InvalidScale(_))
+            ));
+        }
+        viewport.scale = AmplitudeScale::Decibel { floor_db: -60.0 };
+        assert!(viewport.render(5).is_ok());
+    }
+
+    #[test]
+    fn nan_bins_do_not_panic() {
+        let nan = Peak {
+            min: f32::NAN,
+            max: f32::NAN,
+        };
+        let peaks = PeakPyramid::from_base_bins(4, vec![vec![nan; 4]]);
+        let mut rms = RmsEnvelope::new(1, 4);
+        rms.append(&[[0.5; 16]]);
+        let frame = Viewport::new(&peaks, 40).with_rms(&rms).render(4).unwrap();
+        assert_eq!(frame.lanes[0].columns.len(), 4);
+    }
+
+    #[test]
+    fn columns_past_the_audio_are_empty() {
+        let (peaks, _) = analysis();
+        let mut viewport = Viewport::new(&peaks, 40);
+        viewport.zoom = Zoom::Span(2000);
+        let frame = viewport.render(4).unwrap();
+        let columns = &frame.lanes[0].columns;
+        assert!(!columns[1].is_empty());
+        assert!(columns[2].is_empty() && columns[3].is_empty());
+    }
+
+    #[test]
+    fn zero_width_span_maps_to_start() {
+        let (peaks, _) = analysis();
+        let mut viewport = Viewport::new(&peaks, 40);
+        viewport.start_frame = 250;
+        assert_eq!(viewport.x_to_frame(10.0, 0), 250);
+        assert_eq!(viewport.column_start(0, 0), 250);
+        assert!(viewport.render(0).unwrap().lanes[0].columns.is_empty());
+
+        viewport.zoom = Zoom::Span(500);
+        assert_eq!(viewport.x_to_frame(50.0, 100), 500);
+        assert_eq!(viewport.frame_to_x(500, 100), 50.0);
+    }
+}
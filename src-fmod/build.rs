@@ -2,6 +2,10 @@ use std::env;
 use std::fs;
 use std::path::Path;
 
+// The soname of the FMOD SDK's libfmod.so, the name the dynamic loader looks
+// for at run time; bump it with the SDK's major version
+const FMOD_SONAME: &str = "libfmod.so.14";
+
 fn main() {
     let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap();
     let out_dir = env::var("OUT_DIR").unwrap();
@@ -39,6 +43,24 @@ fn main() {
 
             println!("cargo:rustc-link-search=native={}", fmod_dir.display());
             println!("cargo:rustc-link-lib=dylib=fmod");
+
+            // Copy the library under its soname to the output directory, so
+            // binaries run through cargo (tests, examples) find it
+            let out_path = Path::new(&out_dir);
+            let target_dir = out_path
+                .parent() // target/debug/build/driftwave-xxx
+                .and_then(|p| p.parent()) // target/debug/build
+                .and_then(|p| p.parent()) // target/debug
+                .unwrap();
+
+            let so_src = fmod_dir.join("libfmod.so");
+            let so_dst = target_dir.join(FMOD_SONAME);
+
+            if so_src.exists() {
+                fs::copy(&so_src, &so_dst).unwrap();
+                println!("cargo:rerun-if-changed={}", so_src.display());
+            }
+
             // Set rpath for Linux
             println!("cargo:rustc-link-arg=-Wl,-rpath,$ORIGIN/../fmod/linux");
         }
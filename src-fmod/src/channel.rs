@@ -0,0 +1,86 @@
+use crate::dsp::SharedListener;
+use crate::error::fmod_error;
+use crate::ffi::fmod_sys;
+
+use std::cell::Cell;
+use std::ffi::c_void;
+use std::ptr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+thread_local! {
+    // Set while FMOD_System_PlaySound runs; any channel ending meanwhile was stolen
+    static STARTING: Cell<bool> = const { Cell::new(false) };
+}
+
+// Runs `start` with channel ends reported as steals rather than finishes
+pub fn starting<T>(start: impl FnOnce() -> T) -> T {
+    STARTING.with(|starting| starting.set(true));
+    let result = start();
+    STARTING.with(|starting| starting.set(false));
+    result
+}
+
+// Channel callback context, owned by the playback
+pub struct ChannelCallbackData {
+    pub ended: bool,
+    pub listener: Option<SharedListener>,
+    // Set once the sound the channel plays is released, which ends the channel
+    pub released: Option<Arc<AtomicBool>>,
+}
+
+impl ChannelCallbackData {
+    pub fn released(&self) -> bool {
+        self.released
+            .as_ref()
+            .is_some_and(|released| released.load(Ordering::Relaxed))
+    }
+}
+
+// Channel callback that records when FMOD ends the channel. FMOD invokes it
+// synchronously from `FMOD_System_PlaySound` (when stealing), `FMOD_Channel_Stop`
+// or `FMOD_System_Update`, so always on the thread driving the player.
+pub unsafe extern "C" fn channel_callback(
+    channelcontrol: *mut fmod_sys::FMOD_CHANNELCONTROL,
+    _controltype: fmod_sys::FMOD_CHANNELCONTROL_TYPE,
+    callbacktype: fmod_sys::FMOD_CHANNELCONTROL_CALLBACK_TYPE,
+    _commanddata1: *mut c_void,
+    _commanddata2: *mut c_void,
+) -> fmod_sys::FMOD_RESULT {
+    if callbacktype != fmod_sys::FMOD_CHANNELCONTROL_CALLBACK_TYPE_FMOD_CHANNELCONTROL_CALLBACK_END
+    {
+        return fmod_sys::FMOD_RESULT_FMOD_OK;
+    }
+    unsafe {
+        let mut userdata: *mut c_void = ptr::null_mut();
+        let result = fmod_sys::FMOD_Channel_GetUserData(
+            channelcontrol as *mut fmod_sys::FMOD_CHANNEL,
+            &mut userdata,
+        );
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK || userdata.is_null() {
+            return fmod_sys::FMOD_RESULT_FMOD_OK;
+        }
+        let data = &mut *(userdata as *mut ChannelCallbackData);
+        // Releasing the sound tore the channel down; it did not finish
+        if data.released() {
+            return fmod_sys::FMOD_RESULT_FMOD_OK;
+        }
+        let stolen = STARTING.with(|starting| starting.get());
+        if !stolen {
+            data.ended = true;
+        }
+        if let Some(listener) = &data.listener
+            && let Ok(mut listener) = listener.lock()
+        {
+            if stolen {
+                listener.on_error(&fmod_error(
+                    fmod_sys::FMOD_RESULT_FMOD_ERR_CHANNEL_STOLEN,
+                    "Channel was stolen by a newer playback",
+                ));
+            } else {
+                listener.on_finished();
+            }
+        }
+    }
+    fmod_sys::FMOD_RESULT_FMOD_OK
+}
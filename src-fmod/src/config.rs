@@ -0,0 +1,136 @@
+use crate::ffi::fmod_sys;
+
+use std::path::PathBuf;
+
+/// Where the FMOD mixer sends its output.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum FmodOutput {
+    /// The platform's default device, mixed in real time.
+    #[default]
+    Auto,
+    /// No device, and no mixer thread: each `update` mixes one block, so
+    /// playback advances by exactly `block_frames` per call. For machines
+    /// without audio hardware, and tests.
+    NoSoundNrt,
+    /// Like `NoSoundNrt`, but writing the mix to a WAV file at this path,
+    /// complete once the player is dropped.
+    WavWriterNrt(PathBuf),
+}
+
+impl FmodOutput {
+    pub(crate) fn output_type(&self) -> fmod_sys::FMOD_OUTPUTTYPE {
+        match self {
+            FmodOutput::Auto => fmod_sys::FMOD_OUTPUTTYPE_FMOD_OUTPUTTYPE_AUTODETECT,
+            FmodOutput::NoSoundNrt => fmod_sys::FMOD_OUTPUTTYPE_FMOD_OUTPUTTYPE_NOSOUND_NRT,
+            FmodOutput::WavWriterNrt(_) => fmod_sys::FMOD_OUTPUTTYPE_FMOD_OUTPUTTYPE_WAVWRITER_NRT,
+        }
+    }
+}
+
+/// Speaker layout the mixer produces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FmodSpeakerMode {
+    /// The output device's own layout.
+    #[default]
+    Default,
+    Mono,
+    Stereo,
+    Quad,
+    Surround,
+    FivePointOne,
+    SevenPointOne,
+    SevenPointOnePointFour,
+}
+
+impl FmodSpeakerMode {
+    pub(crate) fn speaker_mode(self) -> fmod_sys::FMOD_SPEAKERMODE {
+        match self {
+            FmodSpeakerMode::Default => fmod_sys::FMOD_SPEAKERMODE_FMOD_SPEAKERMODE_DEFAULT,
+            FmodSpeakerMode::Mono => fmod_sys::FMOD_SPEAKERMODE_FMOD_SPEAKERMODE_MONO,
+            FmodSpeakerMode::Stereo => fmod_sys::FMOD_SPEAKERMODE_FMOD_SPEAKERMODE_STEREO,
+            FmodSpeakerMode::Quad => fmod_sys::FMOD_SPEAKERMODE_FMOD_SPEAKERMODE_QUAD,
+            FmodSpeakerMode::Surround => fmod_sys::FMOD_SPEAKERMODE_FMOD_SPEAKERMODE_SURROUND,
+            FmodSpeakerMode::FivePointOne => fmod_sys::FMOD_SPEAKERMODE_FMOD_SPEAKERMODE_5POINT1,
+            FmodSpeakerMode::SevenPointOne => fmod_sys::FMOD_SPEAKERMODE_FMOD_SPEAKERMODE_7POINT1,
+            FmodSpeakerMode::SevenPointOnePointFour => {
+                fmod_sys::FMOD_SPEAKERMODE_FMOD_SPEAKERMODE_7POINT1POINT4
+            }
+        }
+    }
+}
+
+/// How `FmodPlayer::init_with_config` sets up FMOD. Anything left unset
+/// keeps FMOD's default, except as noted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FmodConfig {
+    pub(crate) max_channels: u32,
+    pub(crate) output: FmodOutput,
+    pub(crate) driver: Option<u32>,
+    pub(crate) sample_rate: Option<u32>,
+    pub(crate) speaker_mode: FmodSpeakerMode,
+    pub(crate) dsp_buffer: Option<(u32, u32)>,
+    pub(crate) thread_safe: bool,
+}
+
+impl Default for FmodConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FmodConfig {
+    /// What `init` uses: two channels on the default output, with FMOD's
+    /// thread safety off.
+    pub fn new() -> Self {
+        FmodConfig {
+            max_channels: 2,
+            output: FmodOutput::Auto,
+            driver: None,
+            sample_rate: None,
+            speaker_mode: FmodSpeakerMode::Default,
+            dsp_buffer: None,
+            thread_safe: false,
+        }
+    }
+
+    /// Playbacks that can sound at once; starting one more steals the oldest.
+    pub fn with_max_channels(mut self, max_channels: u32) -> Self {
+        self.max_channels = max_channels;
+        self
+    }
+
+    pub fn with_output(mut self, output: FmodOutput) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Output device, by index among the output's drivers.
+    pub fn with_driver(mut self, driver: u32) -> Self {
+        self.driver = Some(driver);
+        self
+    }
+
+    /// Mixer sample rate in Hz.
+    pub fn with_sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    pub fn with_speaker_mode(mut self, speaker_mode: FmodSpeakerMode) -> Self {
+        self.speaker_mode = speaker_mode;
+        self
+    }
+
+    /// Mix block length in frames, and how many blocks the output buffers.
+    /// Shorter or fewer blocks lower latency; longer or more ride out stalls.
+    pub fn with_dsp_buffer(mut self, length: u32, count: u32) -> Self {
+        self.dsp_buffer = Some((length, count));
+        self
+    }
+
+    /// Lets FMOD be called from several threads, at the cost of internal locking.
+    pub fn with_thread_safe(mut self, thread_safe: bool) -> Self {
+        self.thread_safe = thread_safe;
+        self
+    }
+}
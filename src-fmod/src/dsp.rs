@@ -1,13 +1,46 @@
 use crate::ffi::fmod_sys;
+use crate::window::Timeline;
 use driftwave_core::PlaybackListener;
 
 use std::ffi::c_void;
 use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
-// DSP callback context to pass listener
+// Listener shared between the mixer thread (progress) and the caller's thread (lifecycle)
+pub type SharedListener = Arc<Mutex<Box<dyn PlaybackListener>>>;
+
+// DSP callback context to pass listener; without one the DSP just keeps the timeline current
 pub struct DspCallbackData {
-    pub listener: Option<Box<dyn PlaybackListener>>,
+    pub listener: Option<SharedListener>,
     pub channel: *mut fmod_sys::FMOD_CHANNEL,
+    pub timeline: Arc<Mutex<Timeline>>,
+    // Loop wrap detection; a backward jump after a seek is not a wrap
+    pub looping: bool,
+    pub seeked: AtomicBool,
+    pub last_position: u64,
+    pub pending_loops: u32,
+}
+
+impl DspCallbackData {
+    pub fn new(
+        listener: Option<SharedListener>,
+        channel: *mut fmod_sys::FMOD_CHANNEL,
+        timeline: Arc<Mutex<Timeline>>,
+        looping: bool,
+        start_frame: u64,
+    ) -> Self {
+        DspCallbackData {
+            listener,
+            channel,
+            timeline,
+            looping,
+            seeked: AtomicBool::new(false),
+            // So that a wrap within the first block still counts
+            last_position: start_frame,
+            pending_loops: 0,
+        }
+    }
 }
 
 // DSP callback that reports playback progress
@@ -43,10 +76,32 @@ pub unsafe extern "C" fn progress_dsp_callback(
                         fmod_sys::FMOD_TIMEUNIT_PCM,
                     );
 
+                    // The player holds the timeline while it repositions the channel
+                    let timeline = callback_data.timeline.try_lock();
                     if result == fmod_sys::FMOD_RESULT_FMOD_OK
-                        && let Some(ref mut listener) = callback_data.listener
+                        && let Ok(mut timeline) = timeline
                     {
-                        listener.on_progress(position as u64);
+                        let position = timeline.locate(position as u64);
+                        drop(timeline);
+                        let seeked = callback_data.seeked.swap(false, Ordering::Relaxed);
+                        if callback_data.looping
+                            && !seeked
+                            && position < callback_data.last_position
+                        {
+                            callback_data.pending_loops += 1;
+                        }
+                        callback_data.last_position = position;
+
+                        // Never block the mixer; skip this block if a lifecycle callback holds the lock
+                        if let Some(listener) = &callback_data.listener
+                            && let Ok(mut listener) = listener.try_lock()
+                        {
+                            for _ in 0..callback_data.pending_loops {
+                                listener.on_looped();
+                            }
+                            callback_data.pending_loops = 0;
+                            listener.on_progress(position);
+                        }
                     }
                 }
             }
@@ -1,5 +1,10 @@
+mod channel;
+mod config;
 mod dsp;
+mod error;
 mod ffi;
 mod player;
+mod window;
 
+pub use config::*;
 pub use player::*;
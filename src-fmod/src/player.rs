@@ -10,16 +10,32 @@ unsafe extern "C" {}
 #[link(name = "fmod_vc")]
 unsafe extern "C" {}
 
+use crate::channel;
+use crate::config::{FmodConfig, FmodOutput, FmodSpeakerMode};
 use crate::dsp;
+use crate::error::fmod_error;
 use crate::ffi::fmod_sys;
+use crate::window::{self, DecodedSource, Timeline};
 use async_trait::async_trait;
-use driftwave_core::{Metadata, PlaybackListener, PlaybackState, Player, PlayerError};
+use driftwave_core::decode::{self, Decoder};
+use driftwave_core::{
+    Metadata, PlaybackListener, PlaybackState, Player, PlayerError, PlayerErrorKind, RateMode,
+};
 
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek};
+use std::path::Path;
 use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
 
 pub struct FmodPlayer {
     system: *mut fmod_sys::FMOD_SYSTEM,
+    config: FmodConfig,
+    // Shared with every sound and playback made on `system`, so it is only
+    // released by `init_with_config` once none of them holds FMOD handles
+    handles: Arc<()>,
 }
 
 impl Default for FmodPlayer {
@@ -32,6 +48,299 @@ impl FmodPlayer {
     pub fn new() -> Self {
         FmodPlayer {
             system: ptr::null_mut(),
+            config: FmodConfig::new(),
+            handles: Arc::new(()),
+        }
+    }
+
+    // The system handle, once `init` has created it
+    fn system(&self) -> Result<*mut fmod_sys::FMOD_SYSTEM, PlayerError> {
+        if self.system.is_null() {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::NotInitialized,
+                message: "FMOD player used before init".to_string(),
+            });
+        }
+        Ok(self.system)
+    }
+
+    /// Like `load`, but decodes from disk during playback instead of up
+    /// front, for recordings too long to hold in memory. Seeking stays sample
+    /// accurate, and overlapping playbacks each read the file separately.
+    pub async fn load_stream(&mut self, source: &str) -> Result<FmodSound, PlayerError> {
+        // Past u32 frames FMOD cannot address the file itself, so we decode it
+        if let Ok(file) = File::open(source)
+            && let Ok(decoder) = decode::open(BufReader::new(file))
+            && decoder.metadata().frame_count > u32::MAX as u64
+        {
+            return self.load_decoder(decoder);
+        }
+        self.open(source, fmod_sys::FMOD_CREATESTREAM)
+    }
+
+    /// Streams audio from `decoder`, of any length. Playbacks of the sound
+    /// share the decoder, so starting one stops any other.
+    pub fn load_decoder<R: Read + Seek + 'static>(
+        &mut self,
+        decoder: Box<dyn Decoder<R> + Send>,
+    ) -> Result<FmodSound, PlayerError> {
+        self.system()?;
+        let metadata = decoder.metadata();
+        if metadata.channel_count == 0 || metadata.sample_rate == 0 {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::UnsupportedFormat,
+                message: format!(
+                    "Decoder has {} channels at {} Hz",
+                    metadata.channel_count, metadata.sample_rate
+                ),
+            });
+        }
+        // FMOD needs the length up front, and 0 can mean the decoder does not know it
+        if metadata.frame_count == 0 {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::UnsupportedFormat,
+                message: "Decoder does not report its length".to_string(),
+            });
+        }
+        let window = window::window_frames(metadata.channel_count);
+        let source = Arc::new(Mutex::new(DecodedSource::new(
+            decoder,
+            metadata.channel_count as usize,
+            metadata.frame_count,
+            window,
+        )));
+        self.decoded_sound(source, metadata)
+    }
+
+    // Creates a sound that FMOD streams from `source`
+    fn decoded_sound(
+        &mut self,
+        source: Arc<Mutex<DecodedSource>>,
+        metadata: Metadata,
+    ) -> Result<FmodSound, PlayerError> {
+        let system = self.system()?;
+        let window = window::window_frames(metadata.channel_count);
+        let frame_bytes = metadata.channel_count as u64 * size_of::<f32>() as u64;
+        unsafe {
+            let mut info: fmod_sys::FMOD_CREATESOUNDEXINFO = std::mem::zeroed();
+            info.cbsize = std::mem::size_of::<fmod_sys::FMOD_CREATESOUNDEXINFO>() as i32;
+            info.length = (metadata.frame_count.min(window) * frame_bytes) as u32;
+            info.numchannels = metadata.channel_count as i32;
+            info.defaultfrequency = metadata.sample_rate as i32;
+            info.format = fmod_sys::FMOD_SOUND_FORMAT_FMOD_SOUND_FORMAT_PCMFLOAT;
+            info.pcmreadcallback = Some(window::pcm_read_callback);
+            info.pcmsetposcallback = Some(window::pcm_setpos_callback);
+            // FMOD's reference to the source, dropped when the sound is released
+            info.userdata = Arc::into_raw(source.clone()) as *mut std::ffi::c_void;
+
+            let mut sound: *mut fmod_sys::FMOD_SOUND = ptr::null_mut();
+            let result = fmod_sys::FMOD_System_CreateSound(
+                system,
+                ptr::null(),
+                fmod_sys::FMOD_OPENUSER | fmod_sys::FMOD_CREATESTREAM,
+                &mut info,
+                &mut sound,
+            );
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                drop(Arc::from_raw(info.userdata as *const Mutex<DecodedSource>));
+                return Err(fmod_error(result, "Failed to create decoded sound"));
+            }
+            Ok(FmodSound {
+                ptr: sound,
+                data: None,
+                stream_path: None,
+                stream_channel: ptr::null_mut(),
+                window: if metadata.frame_count > window {
+                    window
+                } else {
+                    0
+                },
+                metadata: Some(metadata),
+                source: Some(source),
+                released: Arc::default(),
+                _handles: self.handles.clone(),
+            })
+        }
+    }
+
+    // Opens the file at `source`, decoded up front or streamed per `open_mode`
+    fn open(
+        &mut self,
+        source: &str,
+        open_mode: fmod_sys::FMOD_MODE,
+    ) -> Result<FmodSound, PlayerError> {
+        let filename = CString::new(source).map_err(|_| PlayerError {
+            kind: PlayerErrorKind::InvalidArgument,
+            message: "Source string contains null byte".to_string(),
+        })?;
+        self.open_path(filename, open_mode)
+    }
+
+    fn open_path(
+        &mut self,
+        filename: CString,
+        open_mode: fmod_sys::FMOD_MODE,
+    ) -> Result<FmodSound, PlayerError> {
+        let system = self.system()?;
+        let sound = unsafe { create_sound_from_file(system, &filename, open_mode)? };
+        let streamed = open_mode & fmod_sys::FMOD_CREATESTREAM != 0;
+        Ok(FmodSound {
+            ptr: sound,
+            data: None,
+            stream_path: streamed.then_some(filename),
+            stream_channel: ptr::null_mut(),
+            window: 0,
+            metadata: None,
+            source: None,
+            released: Arc::default(),
+            _handles: self.handles.clone(),
+        })
+    }
+
+    /// Like `init`, but set up as `config` describes. Any system from an
+    /// earlier `init` is released first, which fails while sounds or
+    /// playbacks made on it are alive. On any other failure the player is
+    /// left uninitialized.
+    pub fn init_with_config(&mut self, config: FmodConfig) -> Result<(), PlayerError> {
+        if Arc::strong_count(&self.handles) > 1 {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidState,
+                message: "Cannot re-initialize while sounds or playbacks are alive".to_string(),
+            });
+        }
+        self.release_system();
+        // The WAV writer takes its file name as the driver data
+        let filename = match &config.output {
+            FmodOutput::WavWriterNrt(path) => Some(
+                path.to_str()
+                    .and_then(|path| CString::new(path).ok())
+                    .ok_or_else(|| PlayerError {
+                        kind: PlayerErrorKind::InvalidArgument,
+                        message: format!("Invalid output path '{}'", path.display()),
+                    })?,
+            ),
+            _ => None,
+        };
+        let driver_data = filename.as_ref().map_or(ptr::null_mut(), |filename| {
+            filename.as_ptr() as *mut std::ffi::c_void
+        });
+        unsafe {
+            let result = fmod_sys::FMOD_System_Create(&mut self.system, fmod_sys::FMOD_VERSION);
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                self.system = ptr::null_mut();
+                return Err(fmod_error(result, "Failed to create FMOD system"));
+            }
+            if let Err(error) = configure_system(self.system, &config, driver_data) {
+                self.release_system();
+                return Err(error);
+            }
+        }
+        self.config = config;
+        Ok(())
+    }
+
+    // Releases the FMOD system, if any, leaving the player uninitialized
+    fn release_system(&mut self) {
+        if !self.system.is_null() {
+            unsafe {
+                let result = fmod_sys::FMOD_System_Release(self.system);
+                if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                    eprintln!("Failed to release FMOD system: {}", result);
+                }
+            }
+            self.system = ptr::null_mut();
+        }
+    }
+
+    /// Renders what `play_range` of `sound` plays to a WAV file at `path`,
+    /// mixing as fast as possible and returning once it is written.
+    /// `configure` first sets up the playback, e.g. its rate and volume. The
+    /// render runs on a separate FMOD system configured like this one but for
+    /// its output, which it leaves undisturbed, playing a copy of the sound's
+    /// samples, its file reopened, or its decoder. A decoded sound has a
+    /// single decoder, so it cannot be rendered while a playback holds it.
+    /// The file may end with a few mix blocks of silence.
+    pub async fn render(
+        &mut self,
+        sound: &FmodSound,
+        start_frame: u64,
+        end_frame: u64,
+        path: impl AsRef<Path>,
+        configure: impl FnOnce(&mut FmodPlayer, &mut FmodPlayback) -> Result<(), PlayerError>,
+    ) -> Result<(), PlayerError> {
+        self.system()?;
+        if sound.source.is_some() && unsafe { channel_active(sound.stream_channel) } {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidState,
+                message: "Cannot render a decoded sound while it is playing".to_string(),
+            });
+        }
+        let mut renderer = FmodPlayer::new();
+        let output = FmodOutput::WavWriterNrt(path.as_ref().to_path_buf());
+        // The device choice belongs to the live output
+        let config = FmodConfig {
+            driver: None,
+            ..self.config.clone()
+        };
+        renderer.init_with_config(config.with_output(output))?;
+        let mut sound = renderer.reopen(sound)?;
+        let mut playback = renderer.play_range(&mut sound, start_frame, end_frame, None)?;
+        configure(&mut renderer, &mut playback)?;
+        // Each update mixes and writes one block
+        while renderer.get_state(&mut playback)? == PlaybackState::Playing {
+            renderer.update()?;
+        }
+        // Releasing the system finishes the file
+        drop(playback);
+        drop(sound);
+        drop(renderer);
+        Ok(())
+    }
+
+    // Recreates `sound`, loaded by another player, on this one
+    fn reopen(&mut self, sound: &FmodSound) -> Result<FmodSound, PlayerError> {
+        if let (Some(source), Some(metadata)) = (&sound.source, sound.metadata) {
+            return self.decoded_sound(source.clone(), metadata);
+        }
+        if let Some(path) = &sound.stream_path {
+            return self.open_path(path.clone(), fmod_sys::FMOD_CREATESTREAM);
+        }
+        let system = self.system()?;
+        let copy = unsafe { copy_sample(system, sound.ptr)? };
+        Ok(FmodSound {
+            ptr: copy,
+            data: None,
+            stream_path: None,
+            stream_channel: ptr::null_mut(),
+            window: 0,
+            metadata: None,
+            source: None,
+            released: Arc::default(),
+            _handles: self.handles.clone(),
+        })
+    }
+
+    /// Frames of output the mixer produces per block.
+    pub fn block_frames(&self) -> Result<u32, PlayerError> {
+        let mut length: u32 = 0;
+        let result = unsafe {
+            fmod_sys::FMOD_System_GetDSPBufferSize(self.system()?, &mut length, ptr::null_mut())
+        };
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to get DSP buffer size"));
+        }
+        Ok(length)
+    }
+
+    /// Runs FMOD's per-frame housekeeping, including end-of-channel
+    /// notifications. Call regularly, e.g. once per UI frame.
+    pub fn update(&mut self) -> Result<(), PlayerError> {
+        unsafe {
+            let result = fmod_sys::FMOD_System_Update(self.system()?);
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                return Err(fmod_error(result, "Failed to update FMOD system"));
+            }
+            Ok(())
         }
     }
 
@@ -40,66 +349,164 @@ impl FmodPlayer {
         sound: &mut FmodSound,
         start_frame: u64,
         end_frame: Option<u64>,
+        loop_range: Option<(u64, u64)>,
         listener: Option<Box<dyn PlaybackListener>>,
     ) -> Result<FmodPlayback, PlayerError> {
+        let system = self.system()?;
+
+        // Ranges are checked before any channel exists
+        let length = self.get_metadata(sound)?.frame_count;
+        let end = end_frame.unwrap_or(length);
+        if start_frame > end || end > length {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!(
+                    "Range {}..{} is outside the sound's {} frames",
+                    start_frame, end, length
+                ),
+            });
+        }
+        if let Some((loop_start, loop_end)) = loop_range
+            && loop_end > length
+        {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!(
+                    "Loop {}..{} is outside the sound's {} frames",
+                    loop_start, loop_end, length
+                ),
+            });
+        }
+
+        // Sounds past u32 frames play window by window, except that a loop
+        // plays within a single window starting at or before it
+        let window = if loop_range.is_none() {
+            sound.window
+        } else {
+            0
+        };
+        let origin = match loop_range {
+            Some((loop_start, loop_end)) if sound.window > 0 => {
+                let origin = start_frame.min(loop_start);
+                if loop_end - origin > sound.window {
+                    return Err(PlayerError {
+                        kind: PlayerErrorKind::InvalidRange,
+                        message: format!(
+                            "Loop {}..{} starting at {} spans more than {} frames",
+                            loop_start, loop_end, start_frame, sound.window
+                        ),
+                    });
+                }
+                origin
+            }
+            _ if window > 0 => start_frame - start_frame % window,
+            _ => 0,
+        };
+        let end_frame = match (end_frame, &sound.metadata) {
+            (None, Some(metadata)) if window > 0 => Some(metadata.frame_count),
+            _ => end_frame,
+        };
+        let timeline = Arc::new(Mutex::new(Timeline::new(origin, window, start_frame)));
+
         unsafe {
+            // A stream has a single read position, so while another playback
+            // holds it this one opens the file again
+            let mut stream: *mut fmod_sys::FMOD_SOUND = ptr::null_mut();
+            if let Some(path) = &sound.stream_path
+                && channel_active(sound.stream_channel)
+            {
+                stream = create_sound_from_file(system, path, fmod_sys::FMOD_CREATESTREAM)?;
+            }
+            let source = if stream.is_null() { sound.ptr } else { stream };
+
             let mut channel: *mut fmod_sys::FMOD_CHANNEL = ptr::null_mut();
-            let result = fmod_sys::FMOD_System_PlaySound(
-                self.system,
-                sound.ptr,
-                ptr::null_mut(),
-                1, // paused
-                &mut channel,
-            );
+            let result = channel::starting(|| {
+                fmod_sys::FMOD_System_PlaySound(
+                    system,
+                    source,
+                    ptr::null_mut(),
+                    1, // paused
+                    &mut channel,
+                )
+            });
             if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                return Err(PlayerError {
-                    message: format!("Failed to play sound: {}", result),
-                });
+                if !stream.is_null() {
+                    fmod_sys::FMOD_Sound_Release(stream);
+                }
+                return Err(fmod_error(result, "Failed to play sound"));
             }
+            // Owns the channel, the stream and all that is attached to them from
+            // here on, so dropping it on any failure stops and releases the lot
+            let mut playback = FmodPlayback {
+                ptr: channel,
+                dsp: ptr::null_mut(),
+                callback_data: ptr::null_mut(),
+                channel_data: ptr::null_mut(),
+                pitch_dsp: ptr::null_mut(),
+                listener: listener.map(|listener| Arc::new(Mutex::new(listener))),
+                end_frame,
+                frequency: 0.0,
+                stream,
+                timeline,
+                source: sound.source.clone(),
+                _handles: self.handles.clone(),
+            };
 
-            // Set position
-            if start_frame > u32::MAX as u64 {
-                return Err(PlayerError {
-                    message: format!("Start frame {} exceeds u32 max", start_frame),
-                });
+            // Rates are applied relative to the sound's own frequency
+            let result = fmod_sys::FMOD_Channel_GetFrequency(channel, &mut playback.frequency);
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                return Err(fmod_error(result, "Failed to get frequency"));
             }
-            let result = fmod_sys::FMOD_Channel_SetPosition(
-                channel,
-                start_frame as u32,
-                fmod_sys::FMOD_TIMEUNIT_PCM,
-            );
+
+            // Volume, mute and pan changes are interpolated over one mix block
+            let result = fmod_sys::FMOD_Channel_SetVolumeRamp(channel, 1);
             if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                return Err(PlayerError {
-                    message: format!("Failed to set position: {}", result),
-                });
+                return Err(fmod_error(result, "Failed to enable volume ramp"));
             }
 
+            if let Some((loop_start, loop_end)) = loop_range {
+                set_loop(channel, loop_start - origin, loop_end - origin)?;
+            } else if window > 0 {
+                set_loop(channel, 0, window)?;
+            }
+
+            // The source is this playback's now that PlaySound stopped any other
+            if let Some(source) = &playback.source {
+                let mut source = source.lock().unwrap_or_else(PoisonError::into_inner);
+                source.advance_on_wrap = window > 0;
+            }
+            set_position(
+                channel,
+                &playback.timeline,
+                playback.source.as_deref(),
+                start_frame,
+            )?;
+
             if let Some(end) = end_frame {
-                let mut parent_clock: u64 = 0;
-                let result =
-                    fmod_sys::FMOD_Channel_GetDSPClock(channel, ptr::null_mut(), &mut parent_clock);
-                if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                    return Err(PlayerError {
-                        message: format!("Failed to get DSP clock: {}", result),
-                    });
-                }
-                let duration_frames = end - start_frame;
-                let stop_clock = parent_clock.saturating_add(duration_frames);
-                let result = fmod_sys::FMOD_Channel_SetDelay(
-                    channel, 0, // start immediately
-                    stop_clock, 1, // stop channels
-                );
-                if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                    return Err(PlayerError {
-                        message: format!("Failed to set delay: {}", result),
-                    });
-                }
+                schedule_stop(channel, start_frame, end)?;
             }
 
-            let mut dsp: *mut fmod_sys::FMOD_DSP = ptr::null_mut();
-            let mut callback_data: *mut dsp::DspCallbackData = ptr::null_mut();
+            playback.channel_data = Box::into_raw(Box::new(channel::ChannelCallbackData {
+                ended: false,
+                listener: playback.listener.clone(),
+                // A playback's own stream outlives the sound
+                released: stream.is_null().then(|| sound.released.clone()),
+            }));
+            let result = fmod_sys::FMOD_Channel_SetUserData(
+                channel,
+                playback.channel_data as *mut std::ffi::c_void,
+            );
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                return Err(fmod_error(result, "Failed to set channel user data"));
+            }
+            let result =
+                fmod_sys::FMOD_Channel_SetCallback(channel, Some(channel::channel_callback));
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                return Err(fmod_error(result, "Failed to set channel callback"));
+            }
 
-            if let Some(listener_box) = listener {
+            // Past u32 frames the timeline needs the DSP to see every wrap
+            if playback.listener.is_some() || window > 0 {
                 let mut dspdesc: fmod_sys::FMOD_DSP_DESCRIPTION = std::mem::zeroed();
                 dspdesc.pluginsdkversion = fmod_sys::FMOD_PLUGIN_SDK_VERSION;
                 let name = b"Progress Tracker\0";
@@ -113,50 +520,428 @@ impl FmodPlayer {
                 dspdesc.numoutputbuffers = 1;
                 dspdesc.read = Some(dsp::progress_dsp_callback);
 
-                let result = fmod_sys::FMOD_System_CreateDSP(self.system, &dspdesc, &mut dsp);
+                let result = fmod_sys::FMOD_System_CreateDSP(system, &dspdesc, &mut playback.dsp);
                 if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                    return Err(PlayerError {
-                        message: format!("Failed to create DSP: {}", result),
-                    });
+                    return Err(fmod_error(result, "Failed to create DSP"));
                 }
-                callback_data = Box::into_raw(Box::new(dsp::DspCallbackData {
-                    listener: Some(listener_box),
+                playback.callback_data = Box::into_raw(Box::new(dsp::DspCallbackData::new(
+                    playback.listener.clone(),
                     channel,
-                }));
+                    playback.timeline.clone(),
+                    loop_range.is_some(),
+                    start_frame,
+                )));
 
-                let result =
-                    fmod_sys::FMOD_DSP_SetUserData(dsp, callback_data as *mut std::ffi::c_void);
+                let result = fmod_sys::FMOD_DSP_SetUserData(
+                    playback.dsp,
+                    playback.callback_data as *mut std::ffi::c_void,
+                );
                 if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                    drop(Box::from_raw(callback_data));
-                    fmod_sys::FMOD_DSP_Release(dsp);
-                    return Err(PlayerError {
-                        message: format!("Failed to set DSP user data: {}", result),
-                    });
+                    return Err(fmod_error(result, "Failed to set DSP user data"));
                 }
 
-                let result = fmod_sys::FMOD_Channel_AddDSP(channel, 0, dsp);
+                let result = fmod_sys::FMOD_Channel_AddDSP(channel, 0, playback.dsp);
                 if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                    drop(Box::from_raw(callback_data));
-                    fmod_sys::FMOD_DSP_Release(dsp);
-                    return Err(PlayerError {
-                        message: format!("Failed to add DSP to channel: {}", result),
-                    });
+                    return Err(fmod_error(result, "Failed to add DSP to channel"));
                 }
             }
 
             let result = fmod_sys::FMOD_Channel_SetPaused(channel, 0);
             if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                return Err(PlayerError {
-                    message: format!("Failed to unpause: {}", result),
-                });
+                return Err(fmod_error(result, "Failed to unpause"));
             }
 
-            Ok(FmodPlayback {
-                ptr: channel,
-                dsp,
-                callback_data,
-            })
+            // Only a playback that started holds the stream or decoder
+            if (sound.stream_path.is_some() || sound.source.is_some()) && stream.is_null() {
+                sound.stream_channel = channel;
+            }
+
+            playback.notify(|listener| listener.on_started());
+            Ok(playback)
+        }
+    }
+
+    // Adds a pitch shifter to `channel` to undo the pitch change of a faster or slower frequency.
+    unsafe fn create_pitch_shifter(
+        &mut self,
+        channel: *mut fmod_sys::FMOD_CHANNEL,
+    ) -> Result<*mut fmod_sys::FMOD_DSP, PlayerError> {
+        unsafe {
+            let mut dsp: *mut fmod_sys::FMOD_DSP = ptr::null_mut();
+            let result = fmod_sys::FMOD_System_CreateDSPByType(
+                self.system,
+                fmod_sys::FMOD_DSP_TYPE_FMOD_DSP_TYPE_PITCHSHIFT,
+                &mut dsp,
+            );
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                return Err(fmod_error(result, "Failed to create pitch shifter"));
+            }
+            let result = fmod_sys::FMOD_Channel_AddDSP(channel, 0, dsp);
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                fmod_sys::FMOD_DSP_Release(dsp);
+                return Err(fmod_error(result, "Failed to add pitch shifter to channel"));
+            }
+            Ok(dsp)
+        }
+    }
+}
+
+// Sets up a newly created `system` as `config` describes and initializes it
+unsafe fn configure_system(
+    system: *mut fmod_sys::FMOD_SYSTEM,
+    config: &FmodConfig,
+    driver_data: *mut std::ffi::c_void,
+) -> Result<(), PlayerError> {
+    unsafe {
+        let result = fmod_sys::FMOD_System_SetOutput(system, config.output.output_type());
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to set FMOD output"));
+        }
+        if let Some(driver) = config.driver {
+            let result = fmod_sys::FMOD_System_SetDriver(system, driver as i32);
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                return Err(fmod_error(
+                    result,
+                    &format!("Failed to select driver {}", driver),
+                ));
+            }
+        }
+        if config.sample_rate.is_some() || config.speaker_mode != FmodSpeakerMode::Default {
+            let mut sample_rate: i32 = 0;
+            let result = fmod_sys::FMOD_System_GetSoftwareFormat(
+                system,
+                &mut sample_rate,
+                ptr::null_mut(),
+                ptr::null_mut(),
+            );
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                return Err(fmod_error(result, "Failed to get software format"));
+            }
+            let result = fmod_sys::FMOD_System_SetSoftwareFormat(
+                system,
+                config.sample_rate.map_or(sample_rate, |rate| rate as i32),
+                config.speaker_mode.speaker_mode(),
+                0,
+            );
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                return Err(fmod_error(result, "Failed to set software format"));
+            }
+        }
+        if let Some((length, count)) = config.dsp_buffer {
+            let result = fmod_sys::FMOD_System_SetDSPBufferSize(system, length, count as i32);
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                return Err(fmod_error(result, "Failed to set DSP buffer size"));
+            }
+        }
+        let mut flags = fmod_sys::FMOD_INIT_NORMAL;
+        if !config.thread_safe {
+            flags |= fmod_sys::FMOD_INIT_THREAD_UNSAFE;
+        }
+        // Without a mixer thread, streams must not wait on a thread of their own
+        // either, or blocks mixed before they are decoded come out silent
+        if config.output != FmodOutput::Auto {
+            flags |= fmod_sys::FMOD_INIT_STREAM_FROM_UPDATE;
+        }
+        let result =
+            fmod_sys::FMOD_System_Init(system, config.max_channels as i32, flags, driver_data);
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to initialize FMOD system"));
+        }
+    }
+    Ok(())
+}
+
+unsafe fn create_sound_from_file(
+    system: *mut fmod_sys::FMOD_SYSTEM,
+    filename: &CStr,
+    open_mode: fmod_sys::FMOD_MODE,
+) -> Result<*mut fmod_sys::FMOD_SOUND, PlayerError> {
+    unsafe {
+        let mut sound: *mut fmod_sys::FMOD_SOUND = ptr::null_mut();
+        // FMOD will interpret this as a file path
+        let result = fmod_sys::FMOD_System_CreateSound(
+            system,
+            filename.as_ptr(),
+            fmod_sys::FMOD_DEFAULT | fmod_sys::FMOD_ACCURATETIME | open_mode,
+            ptr::null_mut(),
+            &mut sound,
+        );
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(
+                result,
+                &format!("Failed to load sound from '{}'", filename.to_string_lossy()),
+            ));
+        }
+        Ok(sound)
+    }
+}
+
+// Creates a sound from an encoded file held in `bytes`. With
+// FMOD_OPENMEMORY_POINT the sound reads `bytes` in place, so they must outlive it.
+unsafe fn create_sound_from_memory(
+    system: *mut fmod_sys::FMOD_SYSTEM,
+    bytes: &[u8],
+    open_mode: fmod_sys::FMOD_MODE,
+) -> Result<*mut fmod_sys::FMOD_SOUND, PlayerError> {
+    if bytes.len() > u32::MAX as usize {
+        return Err(PlayerError {
+            kind: PlayerErrorKind::InvalidRange,
+            message: format!("Sound data of {} bytes exceeds u32 max", bytes.len()),
+        });
+    }
+    unsafe {
+        let mut info: fmod_sys::FMOD_CREATESOUNDEXINFO = std::mem::zeroed();
+        info.cbsize = std::mem::size_of::<fmod_sys::FMOD_CREATESOUNDEXINFO>() as i32;
+        info.length = bytes.len() as u32;
+        let mut sound: *mut fmod_sys::FMOD_SOUND = ptr::null_mut();
+        let result = fmod_sys::FMOD_System_CreateSound(
+            system,
+            bytes.as_ptr() as *const std::ffi::c_char,
+            fmod_sys::FMOD_DEFAULT | fmod_sys::FMOD_ACCURATETIME | open_mode,
+            &mut info,
+            &mut sound,
+        );
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to load sound from memory"));
+        }
+        Ok(sound)
+    }
+}
+
+// Creates a sample on `system` holding the PCM data of `sample`, a sample
+// loaded on any system, in the same format.
+unsafe fn copy_sample(
+    system: *mut fmod_sys::FMOD_SYSTEM,
+    sample: *mut fmod_sys::FMOD_SOUND,
+) -> Result<*mut fmod_sys::FMOD_SOUND, PlayerError> {
+    unsafe {
+        let mut info: fmod_sys::FMOD_CREATESOUNDEXINFO = std::mem::zeroed();
+        info.cbsize = std::mem::size_of::<fmod_sys::FMOD_CREATESOUNDEXINFO>() as i32;
+        let result = fmod_sys::FMOD_Sound_GetFormat(
+            sample,
+            ptr::null_mut(),
+            &mut info.format,
+            &mut info.numchannels,
+            ptr::null_mut(),
+        );
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to get sound format"));
+        }
+        let mut frequency: f32 = 0.0;
+        let result = fmod_sys::FMOD_Sound_GetDefaults(sample, &mut frequency, ptr::null_mut());
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to get sound defaults"));
+        }
+        info.defaultfrequency = frequency as i32;
+        let result = fmod_sys::FMOD_Sound_GetLength(
+            sample,
+            &mut info.length,
+            fmod_sys::FMOD_TIMEUNIT_PCMBYTES,
+        );
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to get sound length"));
+        }
+
+        let (mut ptr1, mut ptr2) = (ptr::null_mut(), ptr::null_mut());
+        let (mut len1, mut len2) = (0, 0);
+        let result = fmod_sys::FMOD_Sound_Lock(
+            sample,
+            0,
+            info.length,
+            &mut ptr1,
+            &mut ptr2,
+            &mut len1,
+            &mut len2,
+        );
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to lock sound data"));
+        }
+        let mut data = Vec::with_capacity((len1 + len2) as usize);
+        data.extend_from_slice(std::slice::from_raw_parts(ptr1 as *const u8, len1 as usize));
+        if !ptr2.is_null() {
+            data.extend_from_slice(std::slice::from_raw_parts(ptr2 as *const u8, len2 as usize));
+        }
+        fmod_sys::FMOD_Sound_Unlock(sample, ptr1, ptr2, len1, len2);
+        info.length = data.len() as u32;
+
+        // FMOD copies the data into the new sample
+        let mut sound: *mut fmod_sys::FMOD_SOUND = ptr::null_mut();
+        let result = fmod_sys::FMOD_System_CreateSound(
+            system,
+            data.as_ptr() as *const std::ffi::c_char,
+            fmod_sys::FMOD_OPENMEMORY | fmod_sys::FMOD_OPENRAW,
+            &mut info,
+            &mut sound,
+        );
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to copy sound"));
+        }
+        Ok(sound)
+    }
+}
+
+// Repeats `loop_start..loop_end` on `channel` until it is stopped.
+unsafe fn set_loop(
+    channel: *mut fmod_sys::FMOD_CHANNEL,
+    loop_start: u64,
+    loop_end: u64,
+) -> Result<(), PlayerError> {
+    if loop_end > u32::MAX as u64 {
+        return Err(PlayerError {
+            kind: PlayerErrorKind::InvalidRange,
+            message: format!("Loop end {} exceeds u32 max", loop_end),
+        });
+    }
+    unsafe {
+        let result = fmod_sys::FMOD_Channel_SetMode(channel, fmod_sys::FMOD_LOOP_NORMAL);
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to set loop mode"));
+        }
+        let result = fmod_sys::FMOD_Channel_SetLoopCount(channel, -1);
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to set loop count"));
+        }
+        // FMOD's loop end is inclusive
+        let result = fmod_sys::FMOD_Channel_SetLoopPoints(
+            channel,
+            loop_start as u32,
+            fmod_sys::FMOD_TIMEUNIT_PCM,
+            (loop_end - 1) as u32,
+            fmod_sys::FMOD_TIMEUNIT_PCM,
+        );
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to set loop points"));
+        }
+        Ok(())
+    }
+}
+
+// Whether `channel` is still playing or paused rather than ended, stolen or never started
+unsafe fn channel_active(channel: *mut fmod_sys::FMOD_CHANNEL) -> bool {
+    if channel.is_null() {
+        return false;
+    }
+    let mut playing: fmod_sys::FMOD_BOOL = 0;
+    let result = unsafe { fmod_sys::FMOD_Channel_IsPlaying(channel, &mut playing) };
+    result == fmod_sys::FMOD_RESULT_FMOD_OK && playing != 0
+}
+
+// Current position of `channel` in PCM frames
+unsafe fn channel_position(channel: *mut fmod_sys::FMOD_CHANNEL) -> Result<u64, PlayerError> {
+    unsafe {
+        let mut position: u32 = 0;
+        let result =
+            fmod_sys::FMOD_Channel_GetPosition(channel, &mut position, fmod_sys::FMOD_TIMEUNIT_PCM);
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to get channel position"));
+        }
+        Ok(position as u64)
+    }
+}
+
+// Moves `channel` to `frame` of its sound. A windowed timeline moves to the
+// window holding `frame`; otherwise positions stay relative to its origin.
+unsafe fn set_position(
+    channel: *mut fmod_sys::FMOD_CHANNEL,
+    timeline: &Mutex<Timeline>,
+    source: Option<&Mutex<DecodedSource>>,
+    frame: u64,
+) -> Result<(), PlayerError> {
+    // Held throughout, so the progress DSP never reads a half-moved channel
+    let mut timeline = timeline.lock().unwrap_or_else(PoisonError::into_inner);
+    let origin = if timeline.window > 0 {
+        frame - frame % timeline.window
+    } else {
+        timeline.origin
+    };
+    let position = match frame.checked_sub(origin) {
+        Some(position) if position <= u32::MAX as u64 => position,
+        _ if origin == 0 => {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!("Frame {} exceeds u32 max", frame),
+            });
+        }
+        _ => {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!("Frame {} is outside the loop window from {}", frame, origin),
+            });
+        }
+    };
+
+    // FMOD seeks a decoded stream through its setpos callback during this call,
+    // so the source must know the new origin first
+    let set_reposition = |reposition: Option<u64>| {
+        if let Some(source) = source {
+            source
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .reposition = reposition;
+        }
+    };
+    set_reposition(Some(origin));
+    let result = unsafe {
+        fmod_sys::FMOD_Channel_SetPosition(channel, position as u32, fmod_sys::FMOD_TIMEUNIT_PCM)
+    };
+    set_reposition(None);
+    if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+        return Err(fmod_error(result, "Failed to set position"));
+    }
+    timeline.origin = origin;
+    timeline.last = frame;
+    Ok(())
+}
+
+// Stops `channel` once it has played from `position` up to `end_frame`.
+unsafe fn schedule_stop(
+    channel: *mut fmod_sys::FMOD_CHANNEL,
+    position: u64,
+    end_frame: u64,
+) -> Result<(), PlayerError> {
+    unsafe {
+        let mut parent_clock: u64 = 0;
+        let result =
+            fmod_sys::FMOD_Channel_GetDSPClock(channel, ptr::null_mut(), &mut parent_clock);
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to get DSP clock"));
+        }
+
+        // The DSP clock counts output samples; the channel consumes source
+        // frames at its own frequency, which the playback rate scales
+        let mut frequency: f32 = 0.0;
+        let result = fmod_sys::FMOD_Channel_GetFrequency(channel, &mut frequency);
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to get frequency"));
         }
+        let mut system: *mut fmod_sys::FMOD_SYSTEM = ptr::null_mut();
+        let result = fmod_sys::FMOD_Channel_GetSystemObject(channel, &mut system);
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to get system object"));
+        }
+        let mut output_rate: i32 = 0;
+        let result = fmod_sys::FMOD_System_GetSoftwareFormat(
+            system,
+            &mut output_rate,
+            ptr::null_mut(),
+            ptr::null_mut(),
+        );
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to get software format"));
+        }
+
+        let duration_frames = end_frame.saturating_sub(position);
+        let duration_clock =
+            (duration_frames as f64 * output_rate as f64 / frequency as f64).round() as u64;
+        let stop_clock = parent_clock.saturating_add(duration_clock);
+        let result = fmod_sys::FMOD_Channel_SetDelay(
+            channel, 0, // start immediately
+            stop_clock, 1, // stop channels
+        );
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+            return Err(fmod_error(result, "Failed to set delay"));
+        }
+        Ok(())
     }
 }
 
@@ -167,49 +952,53 @@ impl Player for FmodPlayer {
     type PlaybackListener = Box<dyn PlaybackListener>;
 
     fn init(&mut self) -> Result<(), PlayerError> {
+        self.init_with_config(FmodConfig::new())
+    }
+
+    async fn load(&mut self, source: &str) -> Result<FmodSound, PlayerError> {
+        self.open(source, fmod_sys::FMOD_CREATESAMPLE)
+    }
+
+    async fn load_bytes(&mut self, bytes: &[u8]) -> Result<FmodSound, PlayerError> {
+        let system = self.system()?;
         unsafe {
-            self.system = ptr::null_mut();
-            let result = fmod_sys::FMOD_System_Create(&mut self.system, fmod_sys::FMOD_VERSION);
-            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                return Err(PlayerError {
-                    message: format!("Failed to create FMOD system: {}", result),
-                });
-            }
-            let result = fmod_sys::FMOD_System_Init(
-                self.system,
-                2,
-                fmod_sys::FMOD_INIT_NORMAL | fmod_sys::FMOD_INIT_THREAD_UNSAFE,
-                ptr::null_mut(),
-            );
-            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                return Err(PlayerError {
-                    message: format!("Failed to initialize FMOD system: {}", result),
-                });
-            }
-            Ok(())
+            let sound = create_sound_from_memory(system, bytes, fmod_sys::FMOD_OPENMEMORY)?;
+            Ok(FmodSound {
+                ptr: sound,
+                data: None,
+                stream_path: None,
+                stream_channel: ptr::null_mut(),
+                window: 0,
+                metadata: None,
+                source: None,
+                released: Arc::default(),
+                _handles: self.handles.clone(),
+            })
         }
     }
 
-    async fn load(&mut self, source: &str) -> Result<FmodSound, PlayerError> {
+    async fn load_owned(&mut self, bytes: Vec<u8>) -> Result<FmodSound, PlayerError> {
+        let system = self.system()?;
+        let cant_point = PlayerErrorKind::BackendSpecific {
+            code: fmod_sys::FMOD_RESULT_FMOD_ERR_MEMORY_CANTPOINT as i32,
+        };
         unsafe {
-            let mut sound: *mut fmod_sys::FMOD_SOUND = ptr::null_mut();
-            let filename = CString::new(source).map_err(|_| PlayerError {
-                message: "Source string contains null byte".to_string(),
-            })?;
-            // FMOD will interpret this as a file path
-            let result = fmod_sys::FMOD_System_CreateSound(
-                self.system,
-                filename.as_ptr(),
-                fmod_sys::FMOD_DEFAULT | fmod_sys::FMOD_ACCURATETIME,
-                ptr::null_mut(),
-                &mut sound,
-            );
-            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                return Err(PlayerError {
-                    message: format!("Failed to load sound from '{}': {}", source, result),
-                });
+            // Only PCM data can be played in place; anything FMOD must decode is copied
+            match create_sound_from_memory(system, &bytes, fmod_sys::FMOD_OPENMEMORY_POINT) {
+                Ok(sound) => Ok(FmodSound {
+                    ptr: sound,
+                    data: Some(bytes),
+                    stream_path: None,
+                    stream_channel: ptr::null_mut(),
+                    window: 0,
+                    metadata: None,
+                    source: None,
+                    released: Arc::default(),
+                    _handles: self.handles.clone(),
+                }),
+                Err(error) if error.kind == cant_point => self.load_bytes(&bytes).await,
+                Err(error) => Err(error),
             }
-            Ok(FmodSound { ptr: sound })
         }
     }
 
@@ -219,7 +1008,7 @@ impl Player for FmodPlayer {
         start_frame: u64,
         listener: Option<Self::PlaybackListener>,
     ) -> Result<FmodPlayback, PlayerError> {
-        self.play_internal(sound, start_frame, None, listener)
+        self.play_internal(sound, start_frame, None, None, listener)
     }
 
     fn play_range(
@@ -229,7 +1018,33 @@ impl Player for FmodPlayer {
         end_frame: u64,
         listener: Option<Self::PlaybackListener>,
     ) -> Result<Self::Playback, PlayerError> {
-        self.play_internal(sound, start_frame, Some(end_frame), listener)
+        self.play_internal(sound, start_frame, Some(end_frame), None, listener)
+    }
+
+    fn play_loop(
+        &mut self,
+        sound: &mut Self::Sound,
+        loop_start: u64,
+        loop_end: u64,
+        start_frame: u64,
+        listener: Option<Self::PlaybackListener>,
+    ) -> Result<Self::Playback, PlayerError> {
+        if loop_start >= loop_end || start_frame >= loop_end {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!(
+                    "Invalid loop {}..{} starting at {}",
+                    loop_start, loop_end, start_frame
+                ),
+            });
+        }
+        self.play_internal(
+            sound,
+            start_frame,
+            None,
+            Some((loop_start, loop_end)),
+            listener,
+        )
     }
 
     fn pause(&mut self, playback: &mut Self::Playback) -> Result<u64, PlayerError> {
@@ -237,29 +1052,208 @@ impl Player for FmodPlayer {
             // First pause the channel
             let result = fmod_sys::FMOD_Channel_SetPaused(playback.ptr, 1);
             if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                return Err(PlayerError {
-                    message: format!("Failed to pause channel: {}", result),
-                });
+                return Err(fmod_error(result, "Failed to pause channel"));
             }
 
-            // Get the current position in PCM frames
-            let mut position: u32 = 0;
-            let result = fmod_sys::FMOD_Channel_GetPosition(
+            // The stop clock keeps counting while paused; resume reschedules it
+            if playback.end_frame.is_some() {
+                let result = fmod_sys::FMOD_Channel_SetDelay(playback.ptr, 0, 0, 1);
+                if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                    return Err(fmod_error(result, "Failed to clear delay"));
+                }
+            }
+
+            let position = playback.position()?;
+            playback.notify(|listener| listener.on_paused(position));
+            Ok(position)
+        }
+    }
+
+    fn resume(&mut self, playback: &mut Self::Playback) -> Result<(), PlayerError> {
+        let state = self.get_state(playback)?;
+        if state == PlaybackState::Playing {
+            return Ok(());
+        }
+        if state != PlaybackState::Paused {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidState,
+                message: format!("Cannot resume a playback that is {:?}", state),
+            });
+        }
+        unsafe {
+            if let Some(end) = playback.end_frame {
+                let position = playback.position()?;
+                schedule_stop(playback.ptr, position, end)?;
+            }
+            let result = fmod_sys::FMOD_Channel_SetPaused(playback.ptr, 0);
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                return Err(fmod_error(result, "Failed to unpause"));
+            }
+            playback.notify(|listener| listener.on_started());
+            Ok(())
+        }
+    }
+
+    fn seek(&mut self, playback: &mut Self::Playback, frame: u64) -> Result<(), PlayerError> {
+        if let Some(end) = playback.end_frame
+            && frame > end
+        {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!("Seek frame {} is past range end {}", frame, end),
+            });
+        }
+        unsafe {
+            set_position(
+                playback.ptr,
+                &playback.timeline,
+                playback.source.as_deref(),
+                frame,
+            )?;
+            if !playback.callback_data.is_null() {
+                (*playback.callback_data)
+                    .seeked
+                    .store(true, Ordering::Relaxed);
+            }
+
+            // The stop clock was computed from the old position
+            if let Some(end) = playback.end_frame {
+                let mut paused: fmod_sys::FMOD_BOOL = 0;
+                let result = fmod_sys::FMOD_Channel_GetPaused(playback.ptr, &mut paused);
+                if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                    return Err(fmod_error(result, "Failed to get paused state"));
+                }
+                if paused == 0 {
+                    schedule_stop(playback.ptr, frame, end)?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn set_volume(
+        &mut self,
+        playback: &mut Self::Playback,
+        volume: f32,
+    ) -> Result<(), PlayerError> {
+        if !(volume >= 0.0 && volume.is_finite()) {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!("Invalid volume {}", volume),
+            });
+        }
+        unsafe {
+            let result = fmod_sys::FMOD_Channel_SetVolume(playback.ptr, volume);
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                return Err(fmod_error(result, "Failed to set volume"));
+            }
+            Ok(())
+        }
+    }
+
+    fn set_muted(&mut self, playback: &mut Self::Playback, muted: bool) -> Result<(), PlayerError> {
+        unsafe {
+            let result = fmod_sys::FMOD_Channel_SetMute(playback.ptr, muted as fmod_sys::FMOD_BOOL);
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                return Err(fmod_error(result, "Failed to set mute"));
+            }
+            Ok(())
+        }
+    }
+
+    fn set_pan(&mut self, playback: &mut Self::Playback, pan: f32) -> Result<(), PlayerError> {
+        if !pan.is_finite() {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!("Invalid pan {}", pan),
+            });
+        }
+        unsafe {
+            let result = fmod_sys::FMOD_Channel_SetPan(playback.ptr, pan.clamp(-1.0, 1.0));
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                return Err(fmod_error(result, "Failed to set pan"));
+            }
+            Ok(())
+        }
+    }
+
+    fn set_playback_rate(
+        &mut self,
+        playback: &mut Self::Playback,
+        rate: f64,
+        mode: RateMode,
+    ) -> Result<(), PlayerError> {
+        if !(rate > 0.0 && rate.is_finite()) {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!("Invalid playback rate {}", rate),
+            });
+        }
+        // The pitch shifter corrects by at most an octave either way
+        if mode == RateMode::PreservePitch && !(0.5..=2.0).contains(&rate) {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!("Pitch-preserving rate {} is outside 0.5..=2.0", rate),
+            });
+        }
+        unsafe {
+            let result = fmod_sys::FMOD_Channel_SetFrequency(
                 playback.ptr,
-                &mut position,
-                fmod_sys::FMOD_TIMEUNIT_PCM,
+                (playback.frequency as f64 * rate) as f32,
             );
             if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                return Err(PlayerError {
-                    message: format!("Failed to get channel position: {}", result),
-                });
+                return Err(fmod_error(result, "Failed to set frequency"));
             }
 
-            Ok(position as u64)
+            match mode {
+                RateMode::Varispeed => {
+                    if !playback.pitch_dsp.is_null() {
+                        let result = fmod_sys::FMOD_DSP_SetBypass(playback.pitch_dsp, 1);
+                        if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                            return Err(fmod_error(result, "Failed to bypass pitch shifter"));
+                        }
+                    }
+                }
+                RateMode::PreservePitch => {
+                    if playback.pitch_dsp.is_null() {
+                        playback.pitch_dsp = self.create_pitch_shifter(playback.ptr)?;
+                    }
+                    let result = fmod_sys::FMOD_DSP_SetParameterFloat(
+                        playback.pitch_dsp,
+                        fmod_sys::FMOD_DSP_PITCHSHIFT_FMOD_DSP_PITCHSHIFT_PITCH as i32,
+                        (1.0 / rate) as f32,
+                    );
+                    if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                        return Err(fmod_error(result, "Failed to set pitch"));
+                    }
+                    let result = fmod_sys::FMOD_DSP_SetBypass(playback.pitch_dsp, 0);
+                    if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                        return Err(fmod_error(result, "Failed to enable pitch shifter"));
+                    }
+                }
+            }
+
+            // The stop clock was computed at the old rate
+            if let Some(end) = playback.end_frame {
+                let mut paused: fmod_sys::FMOD_BOOL = 0;
+                let result = fmod_sys::FMOD_Channel_GetPaused(playback.ptr, &mut paused);
+                if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                    return Err(fmod_error(result, "Failed to get paused state"));
+                }
+                if paused == 0 {
+                    let position = playback.position()?;
+                    schedule_stop(playback.ptr, position, end)?;
+                }
+            }
+            Ok(())
         }
     }
 
     fn get_metadata(&mut self, sound: &mut Self::Sound) -> Result<Metadata, PlayerError> {
+        // FMOD only knows the length of a decoded sound's first window
+        if let Some(metadata) = &sound.metadata {
+            return Ok(*metadata);
+        }
         unsafe {
             let mut sound_type: fmod_sys::FMOD_SOUND_TYPE = 0;
             let mut format: fmod_sys::FMOD_SOUND_FORMAT = 0;
@@ -273,9 +1267,7 @@ impl Player for FmodPlayer {
                 &mut bits,
             );
             if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                return Err(PlayerError {
-                    message: format!("Failed to get sound format: {}", result),
-                });
+                return Err(fmod_error(result, "Failed to get sound format"));
             }
 
             let mut sample_rate: f32 = 0.0;
@@ -283,18 +1275,14 @@ impl Player for FmodPlayer {
             let result =
                 fmod_sys::FMOD_Sound_GetDefaults(sound.ptr, &mut sample_rate, &mut priority);
             if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                return Err(PlayerError {
-                    message: format!("Failed to get sound defaults: {}", result),
-                });
+                return Err(fmod_error(result, "Failed to get sound defaults"));
             }
 
             let mut length: u32 = 0;
             let result =
                 fmod_sys::FMOD_Sound_GetLength(sound.ptr, &mut length, fmod_sys::FMOD_TIMEUNIT_PCM);
             if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                return Err(PlayerError {
-                    message: format!("Failed to get sound length: {}", result),
-                });
+                return Err(fmod_error(result, "Failed to get sound length"));
             }
 
             Ok(Metadata {
@@ -310,52 +1298,83 @@ impl Player for FmodPlayer {
             let mut is_playing: i32 = 0;
             let result = fmod_sys::FMOD_Channel_IsPlaying(playback.ptr, &mut is_playing);
 
-            if result == fmod_sys::FMOD_RESULT_FMOD_ERR_INVALID_HANDLE
-                || result == fmod_sys::FMOD_RESULT_FMOD_ERR_CHANNEL_STOLEN
-            {
+            if result == fmod_sys::FMOD_RESULT_FMOD_ERR_CHANNEL_STOLEN {
+                return Ok(PlaybackState::Stopped);
+            }
+            if result == fmod_sys::FMOD_RESULT_FMOD_ERR_INVALID_HANDLE {
+                // The handle dies once FMOD releases an ended channel
+                let data = &*playback.channel_data;
+                if data.ended {
+                    return Ok(PlaybackState::Finished);
+                }
+                if data.released() {
+                    return Ok(PlaybackState::Stopped);
+                }
                 return Ok(PlaybackState::Invalid);
             }
 
             if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                return Err(PlayerError {
-                    message: format!("Failed to get channel state: {}", result),
-                });
+                return Err(fmod_error(result, "Failed to get channel state"));
+            }
+            if is_playing == 0 {
+                return Ok(PlaybackState::Finished);
             }
 
-            if is_playing != 0 {
-                Ok(PlaybackState::Playing)
-            } else {
-                Ok(PlaybackState::NotPlaying)
+            let mut paused: fmod_sys::FMOD_BOOL = 0;
+            let result = fmod_sys::FMOD_Channel_GetPaused(playback.ptr, &mut paused);
+            if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                return Err(fmod_error(result, "Failed to get paused state"));
             }
+            if paused != 0 {
+                return Ok(PlaybackState::Paused);
+            }
+
+            // A range stays "playing" for a mix block or two after its stop clock
+            if let Some(end) = playback.end_frame
+                && playback.position()? >= end
+            {
+                return Ok(PlaybackState::Finished);
+            }
+            Ok(PlaybackState::Playing)
         }
     }
 }
 
 impl Drop for FmodPlayer {
     fn drop(&mut self) {
-        if !self.system.is_null() {
-            unsafe {
-                let result = fmod_sys::FMOD_System_Release(self.system);
-                if result != fmod_sys::FMOD_RESULT_FMOD_OK {
-                    eprintln!("Failed to release FMOD system: {}", result);
-                }
-            }
-        }
+        self.release_system();
     }
 }
 
 pub struct FmodSound {
     ptr: *mut fmod_sys::FMOD_SOUND,
+    // Encoded file FMOD reads in place, for sounds opened with FMOD_OPENMEMORY_POINT
+    data: Option<Vec<u8>>,
+    // For streams, the file to reopen when `ptr` is busy, and the channel last playing `ptr`
+    stream_path: Option<CString>,
+    stream_channel: *mut fmod_sys::FMOD_CHANNEL,
+    // Frames per window for decoded sounds too long to play in one; 0 otherwise
+    window: u64,
+    // For decoded sounds, the decoder's metadata and the decoder itself
+    metadata: Option<Metadata>,
+    source: Option<Arc<Mutex<DecodedSource>>>,
+    // Set before `ptr` is released, so the channels it ends count as stopped
+    released: Arc<AtomicBool>,
+    _handles: Arc<()>,
 }
 
 impl Drop for FmodSound {
     fn drop(&mut self) {
         if !self.ptr.is_null() {
+            self.released.store(true, Ordering::Relaxed);
             unsafe {
                 let result = fmod_sys::FMOD_Sound_Release(self.ptr);
                 if result != fmod_sys::FMOD_RESULT_FMOD_OK {
                     eprintln!("Failed to release FMOD sound: {}", result);
                 }
+                if let Some(source) = &self.source {
+                    drop(Arc::from_raw(Arc::as_ptr(source)));
+                }
             }
         }
     }
@@ -365,12 +1384,43 @@ pub struct FmodPlayback {
     ptr: *mut fmod_sys::FMOD_CHANNEL,
     dsp: *mut fmod_sys::FMOD_DSP,
     callback_data: *mut dsp::DspCallbackData,
+    channel_data: *mut channel::ChannelCallbackData,
+    pitch_dsp: *mut fmod_sys::FMOD_DSP,
+    listener: Option<dsp::SharedListener>,
+    end_frame: Option<u64>,
+    // The channel's frequency at rate 1
+    frequency: f32,
+    // A second opening of a streamed sound, owned by this playback
+    stream: *mut fmod_sys::FMOD_SOUND,
+    timeline: Arc<Mutex<Timeline>>,
+    source: Option<Arc<Mutex<DecodedSource>>>,
+    _handles: Arc<()>,
+}
+
+impl FmodPlayback {
+    // Current frame of the sound
+    unsafe fn position(&self) -> Result<u64, PlayerError> {
+        let position = unsafe { channel_position(self.ptr)? };
+        let mut timeline = self.timeline.lock().unwrap_or_else(PoisonError::into_inner);
+        Ok(timeline.locate(position))
+    }
+
+    fn notify(&self, event: impl FnOnce(&mut dyn PlaybackListener)) {
+        if let Some(listener) = &self.listener
+            && let Ok(mut listener) = listener.lock()
+        {
+            event(listener.as_mut());
+        }
+    }
 }
 
 impl Drop for FmodPlayback {
     fn drop(&mut self) {
         unsafe {
             if !self.ptr.is_null() {
+                // Detach our callback first; FMOD may report the end after channel_data is freed
+                fmod_sys::FMOD_Channel_SetCallback(self.ptr, None);
+                fmod_sys::FMOD_Channel_SetUserData(self.ptr, ptr::null_mut());
                 let result = fmod_sys::FMOD_Channel_Stop(self.ptr);
                 if result != fmod_sys::FMOD_RESULT_FMOD_OK
                     && result != fmod_sys::FMOD_RESULT_FMOD_ERR_INVALID_HANDLE
@@ -387,9 +1437,27 @@ impl Drop for FmodPlayback {
                 }
             }
 
+            if !self.pitch_dsp.is_null() {
+                let result = fmod_sys::FMOD_DSP_Release(self.pitch_dsp);
+                if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                    eprintln!("Failed to release FMOD pitch shifter: {}", result);
+                }
+            }
+
+            if !self.stream.is_null() {
+                let result = fmod_sys::FMOD_Sound_Release(self.stream);
+                if result != fmod_sys::FMOD_RESULT_FMOD_OK {
+                    eprintln!("Failed to release FMOD stream: {}", result);
+                }
+            }
+
             if !self.callback_data.is_null() {
                 drop(Box::from_raw(self.callback_data));
             }
+
+            if !self.channel_data.is_null() {
+                drop(Box::from_raw(self.channel_data));
+            }
         }
     }
 }
@@ -0,0 +1,192 @@
+use crate::ffi::fmod_sys;
+use driftwave_core::decode::{DecodeError, Decoder};
+
+use std::ffi::c_void;
+use std::io::{Read, Seek};
+use std::ptr;
+use std::sync::Mutex;
+
+// FMOD addresses a sound in u32 PCM frames, and a user-created sound's length
+// in u32 bytes. A decoded sound longer than that plays as a window of this many
+// frames that FMOD loops while the decoder moves on by a window at each wrap.
+// A power of two, so every multiple of 2^32 frames is a window boundary.
+pub fn window_frames(channel_count: u32) -> u64 {
+    let frame_bytes = channel_count.max(1) as u64 * size_of::<f32>() as u64;
+    1 << (u32::MAX as u64 / frame_bytes).ilog2()
+}
+
+// Erases the reader type a decoder was opened on
+trait FrameReader: Send {
+    fn read(&mut self, buffers: &mut [Vec<f32>], max_frames: usize) -> Result<usize, DecodeError>;
+    fn seek(&mut self, frame: u64) -> Result<(), DecodeError>;
+}
+
+impl<R: Read + Seek> FrameReader for Box<dyn Decoder<R> + Send> {
+    fn read(&mut self, buffers: &mut [Vec<f32>], max_frames: usize) -> Result<usize, DecodeError> {
+        self.as_mut().read(buffers, max_frames)
+    }
+
+    fn seek(&mut self, frame: u64) -> Result<(), DecodeError> {
+        self.as_mut().seek(frame)
+    }
+}
+
+// Decoder feeding an FMOD_OPENUSER stream, locked by FMOD's stream thread while
+// it reads and by the player while it repositions the channel
+pub struct DecodedSource {
+    reader: Box<dyn FrameReader>,
+    buffers: Vec<Vec<f32>>,
+    frame_count: u64,
+    window: u64,
+    // Frame at FMOD position 0 of the window being decoded
+    origin: u64,
+    // Origin for the seek FMOD makes when the player repositions the channel. Any
+    // further seek within that call is FMOD wrapping the window it prefilled.
+    pub reposition: Option<u64>,
+    // Whether a seek FMOD makes on its own is the window wrapping rather than a loop repeating
+    pub advance_on_wrap: bool,
+}
+
+impl DecodedSource {
+    pub fn new<R: Read + Seek + 'static>(
+        decoder: Box<dyn Decoder<R> + Send>,
+        channel_count: usize,
+        frame_count: u64,
+        window: u64,
+    ) -> Self {
+        DecodedSource {
+            reader: Box::new(decoder),
+            buffers: vec![Vec::new(); channel_count],
+            frame_count,
+            window,
+            origin: 0,
+            reposition: None,
+            advance_on_wrap: false,
+        }
+    }
+
+    // Fills interleaved `samples`, with silence past the end
+    fn fill(&mut self, samples: &mut [f32]) -> Result<(), DecodeError> {
+        let channels = self.buffers.len();
+        let frames = samples.len() / channels;
+        let mut written = 0;
+        while written < frames {
+            let read = self.reader.read(&mut self.buffers, frames - written)?;
+            if read == 0 {
+                break;
+            }
+            for (i, frame) in samples[written * channels..]
+                .chunks_exact_mut(channels)
+                .take(read)
+                .enumerate()
+            {
+                for (sample, buffer) in frame.iter_mut().zip(&self.buffers) {
+                    *sample = buffer[i];
+                }
+            }
+            written += read;
+        }
+        samples[written * channels..].fill(0.0);
+        Ok(())
+    }
+
+    fn set_position(&mut self, position: u64) -> Result<(), DecodeError> {
+        self.origin = match self.reposition.take() {
+            Some(origin) => origin,
+            None if self.advance_on_wrap => self.origin + self.window,
+            None => self.origin,
+        };
+        self.reader
+            .seek((self.origin + position).min(self.frame_count))
+    }
+}
+
+unsafe fn source_of<'a>(sound: *mut fmod_sys::FMOD_SOUND) -> Option<&'a Mutex<DecodedSource>> {
+    unsafe {
+        let mut userdata: *mut c_void = ptr::null_mut();
+        let result = fmod_sys::FMOD_Sound_GetUserData(sound, &mut userdata);
+        if result != fmod_sys::FMOD_RESULT_FMOD_OK || userdata.is_null() {
+            return None;
+        }
+        Some(&*(userdata as *const Mutex<DecodedSource>))
+    }
+}
+
+// PCM read callback; FMOD calls it from its stream thread
+pub unsafe extern "C" fn pcm_read_callback(
+    sound: *mut fmod_sys::FMOD_SOUND,
+    data: *mut c_void,
+    datalen: ::core::ffi::c_uint,
+) -> fmod_sys::FMOD_RESULT {
+    unsafe {
+        let Some(source) = source_of(sound) else {
+            return fmod_sys::FMOD_RESULT_FMOD_ERR_INVALID_PARAM;
+        };
+        let Ok(mut source) = source.lock() else {
+            return fmod_sys::FMOD_RESULT_FMOD_ERR_INTERNAL;
+        };
+        let samples =
+            std::slice::from_raw_parts_mut(data as *mut f32, datalen as usize / size_of::<f32>());
+        match source.fill(samples) {
+            Ok(()) => fmod_sys::FMOD_RESULT_FMOD_OK,
+            Err(_) => fmod_sys::FMOD_RESULT_FMOD_ERR_FILE_BAD,
+        }
+    }
+}
+
+// PCM seek callback, for the player's seeks and for FMOD's own at loop wraps
+pub unsafe extern "C" fn pcm_setpos_callback(
+    sound: *mut fmod_sys::FMOD_SOUND,
+    _subsound: ::core::ffi::c_int,
+    position: ::core::ffi::c_uint,
+    postype: fmod_sys::FMOD_TIMEUNIT,
+) -> fmod_sys::FMOD_RESULT {
+    unsafe {
+        let Some(source) = source_of(sound) else {
+            return fmod_sys::FMOD_RESULT_FMOD_ERR_INVALID_PARAM;
+        };
+        let Ok(mut source) = source.lock() else {
+            return fmod_sys::FMOD_RESULT_FMOD_ERR_INTERNAL;
+        };
+        let mut position = position as u64;
+        if postype == fmod_sys::FMOD_TIMEUNIT_PCMBYTES {
+            position /= (source.buffers.len() * size_of::<f32>()) as u64;
+        }
+        match source.set_position(position) {
+            Ok(()) => fmod_sys::FMOD_RESULT_FMOD_OK,
+            Err(_) => fmod_sys::FMOD_RESULT_FMOD_ERR_FILE_COULDNOTSEEK,
+        }
+    }
+}
+
+// Maps a channel's FMOD positions to frames of the whole sound, shared by the
+// player and the progress DSP
+pub struct Timeline {
+    // Frame at FMOD position 0
+    pub origin: u64,
+    // Frames the origin moves on by each time FMOD wraps; 0 when it never does
+    pub window: u64,
+    // Last frame located, to tell how many wraps have passed
+    pub last: u64,
+}
+
+impl Timeline {
+    pub fn new(origin: u64, window: u64, frame: u64) -> Self {
+        Timeline {
+            origin,
+            window,
+            last: frame,
+        }
+    }
+
+    // Frame for FMOD `position`, assuming less than half a window has played since the last
+    pub fn locate(&mut self, position: u64) -> u64 {
+        let mut frame = self.origin + position;
+        if self.window > 0 && self.last > frame {
+            let wraps = (self.last - frame + self.window / 2) / self.window;
+            frame += wraps * self.window;
+        }
+        self.last = frame;
+        frame
+    }
+}
@@ -0,0 +1,218 @@
+//! Sounds past FMOD's u32 frame positions, fed through FMOD_OPENUSER by a
+//! synthetic decoder that never holds more than a block of audio.
+
+use driftwave_core::decode::{DecodeError, Decoder};
+use driftwave_core::{Metadata, PlaybackListener, PlaybackState, Player, PlayerErrorKind};
+use driftwave_fmod::{FmodConfig, FmodOutput, FmodPlayer, FmodSound};
+
+use std::io::Empty;
+use std::sync::{Arc, Mutex};
+
+const SAMPLE_RATE: u32 = 48000;
+// About 50 hours, well past u32::MAX frames
+const FRAME_COUNT: u64 = (1 << 33) + 12345;
+const BOUNDARY: u64 = 1 << 32;
+
+// A quiet sawtooth, with every seek recorded
+struct Synthetic {
+    position: u64,
+    seeks: Arc<Mutex<Vec<u64>>>,
+}
+
+impl Decoder<Empty> for Synthetic {
+    fn open(_reader: Empty) -> Result<Self, DecodeError> {
+        Err(DecodeError::Unsupported("synthetic".to_string()))
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            sample_rate: SAMPLE_RATE,
+            channel_count: 1,
+            frame_count: FRAME_COUNT,
+        }
+    }
+
+    fn position(&self) -> u64 {
+        self.position
+    }
+
+    fn read(&mut self, buffers: &mut [Vec<f32>], max_frames: usize) -> Result<usize, DecodeError> {
+        let frames = (FRAME_COUNT - self.position).min(max_frames as u64) as usize;
+        let start = self.position;
+        buffers[0].clear();
+        buffers[0].extend((0..frames as u64).map(|i| ((start + i) % 96) as f32 / 960.0));
+        self.position += frames as u64;
+        Ok(frames)
+    }
+
+    fn seek(&mut self, frame: u64) -> Result<(), DecodeError> {
+        self.position = frame;
+        self.seeks.lock().unwrap().push(frame);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Events {
+    progress: Vec<u64>,
+    loops: u32,
+    finished: bool,
+}
+
+struct Recorder(Arc<Mutex<Events>>);
+
+impl PlaybackListener for Recorder {
+    fn on_progress(&mut self, position_frames: u64) {
+        self.0.lock().unwrap().progress.push(position_frames);
+    }
+
+    fn on_finished(&mut self) {
+        self.0.lock().unwrap().finished = true;
+    }
+
+    fn on_looped(&mut self) {
+        self.0.lock().unwrap().loops += 1;
+    }
+}
+
+fn load() -> (FmodPlayer, FmodSound, Arc<Mutex<Vec<u64>>>, u64) {
+    let mut player = FmodPlayer::new();
+    player
+        .init_with_config(FmodConfig::new().with_output(FmodOutput::NoSoundNrt))
+        .unwrap();
+    let seeks = Arc::new(Mutex::new(Vec::new()));
+    let decoder = Synthetic {
+        position: 0,
+        seeks: seeks.clone(),
+    };
+    let sound = player.load_decoder(Box::new(decoder)).unwrap();
+    let block_frames = player.block_frames().unwrap() as u64;
+    (player, sound, seeks, block_frames)
+}
+
+fn recorder() -> (Option<Box<dyn PlaybackListener>>, Arc<Mutex<Events>>) {
+    let events = Arc::new(Mutex::new(Events::default()));
+    (Some(Box::new(Recorder(events.clone()))), events)
+}
+
+// Each update mixes exactly one block under the non-realtime output
+fn advance(player: &mut FmodPlayer, blocks: u64) {
+    for _ in 0..blocks {
+        player.update().unwrap();
+    }
+}
+
+#[test]
+fn metadata_reports_full_length() {
+    let (mut player, mut sound, _, _) = load();
+    let metadata = player.get_metadata(&mut sound).unwrap();
+    assert_eq!(metadata.frame_count, FRAME_COUNT);
+    assert_eq!(metadata.sample_rate, SAMPLE_RATE);
+    assert_eq!(metadata.channel_count, 1);
+}
+
+#[test]
+fn plays_and_pauses_past_u32_max() {
+    let (mut player, mut sound, seeks, block_frames) = load();
+    let start = u32::MAX as u64 + 1_000_000;
+    let mut playback = player.play_from(&mut sound, start, None).unwrap();
+    assert_eq!(seeks.lock().unwrap().last(), Some(&start));
+
+    advance(&mut player, 3);
+    assert_eq!(
+        player.pause(&mut playback).unwrap(),
+        start + 3 * block_frames
+    );
+}
+
+#[test]
+fn range_plays_across_window_boundary() {
+    let (mut player, mut sound, seeks, block_frames) = load();
+    let (start, end) = (BOUNDARY - 4800, BOUNDARY + 4800);
+    let (listener, events) = recorder();
+    let mut playback = player.play_range(&mut sound, start, end, listener).unwrap();
+
+    let blocks = (end - start).div_ceil(block_frames);
+    advance(&mut player, blocks - 1);
+    assert_eq!(
+        player.get_state(&mut playback).unwrap(),
+        PlaybackState::Playing
+    );
+    advance(&mut player, 1);
+    assert_eq!(
+        player.get_state(&mut playback).unwrap(),
+        PlaybackState::Finished
+    );
+
+    // The decoder moved on to the next window rather than repeating this one
+    assert!(seeks.lock().unwrap().contains(&BOUNDARY));
+    // FMOD reports the channel's end a few updates after it stops
+    advance(&mut player, 4);
+    let events = events.lock().unwrap();
+    // A stream's channel reports where each block starts rather than ends
+    let expected: Vec<u64> = (0..blocks)
+        .map(|block| start + block * block_frames)
+        .chain([end])
+        .collect();
+    assert_eq!(events.progress[..=blocks as usize], expected);
+    assert!(
+        events.progress[blocks as usize..]
+            .iter()
+            .all(|&frame| frame == end)
+    );
+    assert_eq!(events.loops, 0);
+    assert!(events.finished);
+}
+
+#[test]
+fn seeks_past_u32_max() {
+    let (mut player, mut sound, seeks, block_frames) = load();
+    let mut playback = player.play_from(&mut sound, 0, None).unwrap();
+    let target = 3 * BOUNDARY / 2 + 500;
+    player.seek(&mut playback, target).unwrap();
+    assert_eq!(seeks.lock().unwrap().last(), Some(&target));
+
+    advance(&mut player, 2);
+    assert_eq!(
+        player.pause(&mut playback).unwrap(),
+        target + 2 * block_frames
+    );
+}
+
+#[test]
+fn loops_past_u32_max() {
+    let (mut player, mut sound, _, block_frames) = load();
+    let (loop_start, loop_end) = (BOUNDARY + 1000, BOUNDARY + 1000 + 2400);
+    let length = loop_end - loop_start;
+    let (listener, events) = recorder();
+    let mut playback = player
+        .play_loop(&mut sound, loop_start, loop_end, loop_start, listener)
+        .unwrap();
+
+    let blocks = 8;
+    advance(&mut player, blocks);
+    let played = blocks * block_frames;
+    assert_eq!(
+        player.pause(&mut playback).unwrap(),
+        loop_start + played % length
+    );
+    // Progress, and with it loop counting, trails the mix by a block
+    let events = events.lock().unwrap();
+    let reported = (blocks - 1) * block_frames;
+    let expected: Vec<u64> = (0..blocks)
+        .map(|block| loop_start + block * block_frames % length)
+        .collect();
+    assert_eq!(events.progress, expected);
+    assert_eq!(events.loops as u64, reported / length);
+    assert!(!events.finished);
+}
+
+#[test]
+fn rejects_loop_wider_than_window() {
+    let (mut player, mut sound, _, _) = load();
+    let error = player
+        .play_loop(&mut sound, 0, FRAME_COUNT, 0, None)
+        .err()
+        .unwrap();
+    assert_eq!(error.kind, PlayerErrorKind::InvalidRange);
+}
@@ -0,0 +1,485 @@
+//! Playback under the non-realtime no-sound output, where each `update`
+//! mixes exactly one block, so positions and callbacks are deterministic.
+
+use driftwave_core::{PlaybackListener, PlaybackState, Player, PlayerErrorKind, RateMode};
+use driftwave_fmod::{FmodConfig, FmodOutput, FmodPlayer, FmodSound};
+
+use std::future::Future;
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+const SAMPLE_RATE: u32 = 48000;
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Started,
+    Progress(u64),
+    Paused(u64),
+    Finished,
+    Looped,
+}
+
+type Events = Arc<Mutex<Vec<Event>>>;
+
+struct Recorder(Events);
+
+impl PlaybackListener for Recorder {
+    fn on_progress(&mut self, position_frames: u64) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(Event::Progress(position_frames));
+    }
+
+    fn on_started(&mut self) {
+        self.0.lock().unwrap().push(Event::Started);
+    }
+
+    fn on_paused(&mut self, position_frames: u64) {
+        self.0.lock().unwrap().push(Event::Paused(position_frames));
+    }
+
+    fn on_finished(&mut self) {
+        self.0.lock().unwrap().push(Event::Finished);
+    }
+
+    fn on_looped(&mut self) {
+        self.0.lock().unwrap().push(Event::Looped);
+    }
+}
+
+fn recorder() -> (Option<Box<dyn PlaybackListener>>, Events) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    (Some(Box::new(Recorder(events.clone()))), events)
+}
+
+// The FMOD player's futures never wait, so a single poll completes them
+fn block<F: Future>(future: F) -> F::Output {
+    match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future did not complete"),
+    }
+}
+
+// One second of 16-bit mono WAV at the output rate, so frames map one to one
+fn wav() -> Vec<u8> {
+    let frames = SAMPLE_RATE;
+    let data = frames * 2;
+    let mut bytes = Vec::new();
+    bytes.extend(b"RIFF");
+    bytes.extend((36 + data).to_le_bytes());
+    bytes.extend(b"WAVEfmt ");
+    bytes.extend(16u32.to_le_bytes());
+    bytes.extend(1u16.to_le_bytes()); // PCM
+    bytes.extend(1u16.to_le_bytes()); // mono
+    bytes.extend(SAMPLE_RATE.to_le_bytes());
+    bytes.extend((SAMPLE_RATE * 2).to_le_bytes());
+    bytes.extend(2u16.to_le_bytes());
+    bytes.extend(16u16.to_le_bytes());
+    bytes.extend(b"data");
+    bytes.extend(data.to_le_bytes());
+    for i in 0..frames {
+        let sample = ((i as f32 / 20.0).sin() * 10000.0) as i16;
+        bytes.extend(sample.to_le_bytes());
+    }
+    bytes
+}
+
+fn load() -> (FmodPlayer, FmodSound, u64) {
+    load_with(FmodConfig::new())
+}
+
+fn load_with(config: FmodConfig) -> (FmodPlayer, FmodSound, u64) {
+    let mut player = FmodPlayer::new();
+    player
+        .init_with_config(config.with_output(FmodOutput::NoSoundNrt))
+        .unwrap();
+    let sound = block(player.load_bytes(&wav())).unwrap();
+    let block_frames = player.block_frames().unwrap() as u64;
+    (player, sound, block_frames)
+}
+
+fn advance(player: &mut FmodPlayer, blocks: u32) {
+    for _ in 0..blocks {
+        player.update().unwrap();
+    }
+}
+
+#[test]
+fn play_range_stops_at_end_frame() {
+    let (mut player, mut sound, block_frames) = load();
+    let (start, end) = (1000, 1000 + 4 * block_frames + 100);
+    let (listener, events) = recorder();
+    let mut playback = player.play_range(&mut sound, start, end, listener).unwrap();
+
+    advance(&mut player, 4);
+    assert_eq!(
+        player.get_state(&mut playback).unwrap(),
+        PlaybackState::Playing
+    );
+    advance(&mut player, 1);
+    assert_eq!(
+        player.get_state(&mut playback).unwrap(),
+        PlaybackState::Finished
+    );
+
+    // FMOD reports the channel's end a few updates after it stops
+    advance(&mut player, 4);
+    let events = events.lock().unwrap();
+    let progress: Vec<u64> = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Progress(frame) => Some(*frame),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        progress[..5],
+        [
+            start + block_frames,
+            start + 2 * block_frames,
+            start + 3 * block_frames,
+            start + 4 * block_frames,
+            end,
+        ]
+    );
+    assert!(progress[5..].iter().all(|&frame| frame == end));
+    assert_eq!(events.first(), Some(&Event::Started));
+    assert_eq!(events.last(), Some(&Event::Finished));
+}
+
+#[test]
+fn pause_reports_exact_position() {
+    let (mut player, mut sound, block_frames) = load();
+    let mut playback = player.play_from(&mut sound, 1000, None).unwrap();
+
+    advance(&mut player, 3);
+    assert_eq!(
+        player.pause(&mut playback).unwrap(),
+        1000 + 3 * block_frames
+    );
+    assert_eq!(
+        player.get_state(&mut playback).unwrap(),
+        PlaybackState::Paused
+    );
+
+    advance(&mut player, 3);
+    player.resume(&mut playback).unwrap();
+    advance(&mut player, 2);
+    assert_eq!(
+        player.pause(&mut playback).unwrap(),
+        1000 + 5 * block_frames
+    );
+}
+
+#[test]
+fn listener_sees_lifecycle_in_order() {
+    let (mut player, mut sound, block_frames) = load();
+    let end = 2 * block_frames;
+    let (listener, events) = recorder();
+    let mut playback = player.play_range(&mut sound, 0, end, listener).unwrap();
+
+    advance(&mut player, 1);
+    player.pause(&mut playback).unwrap();
+    player.resume(&mut playback).unwrap();
+    advance(&mut player, 5);
+
+    let events = events.lock().unwrap();
+    assert_eq!(
+        events[..5],
+        [
+            Event::Started,
+            Event::Progress(block_frames),
+            Event::Paused(block_frames),
+            Event::Started,
+            Event::Progress(end),
+        ]
+    );
+    assert_eq!(events.last(), Some(&Event::Finished));
+}
+
+#[test]
+fn resume_while_playing_does_not_notify() {
+    let (mut player, mut sound, block_frames) = load();
+    let (listener, events) = recorder();
+    let mut playback = player.play_from(&mut sound, 0, listener).unwrap();
+
+    advance(&mut player, 1);
+    player.resume(&mut playback).unwrap();
+    advance(&mut player, 1);
+
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            Event::Started,
+            Event::Progress(block_frames),
+            Event::Progress(2 * block_frames),
+        ]
+    );
+}
+
+#[test]
+fn releasing_sound_stops_rather_than_finishes() {
+    let (mut player, _, _) = load();
+    // Releasing a stream ends its channel through the end callback
+    let path = std::env::temp_dir().join("driftwave-nrt-release.wav");
+    std::fs::write(&path, wav()).unwrap();
+    let mut sound = block(player.load_stream(path.to_str().unwrap())).unwrap();
+    let (listener, events) = recorder();
+    let mut playback = player.play_from(&mut sound, 0, listener).unwrap();
+    advance(&mut player, 1);
+
+    drop(sound);
+    advance(&mut player, 2);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        player.get_state(&mut playback).unwrap(),
+        PlaybackState::Stopped
+    );
+    assert!(!events.lock().unwrap().contains(&Event::Finished));
+}
+
+#[test]
+fn seek_while_paused_resumes_from_target() {
+    let (mut player, mut sound, block_frames) = load();
+    let (listener, events) = recorder();
+    let mut playback = player.play_from(&mut sound, 0, listener).unwrap();
+
+    advance(&mut player, 2);
+    player.pause(&mut playback).unwrap();
+    player.seek(&mut playback, 20000).unwrap();
+    player.resume(&mut playback).unwrap();
+    advance(&mut player, 1);
+
+    assert_eq!(
+        events.lock().unwrap().last(),
+        Some(&Event::Progress(20000 + block_frames))
+    );
+}
+
+#[test]
+fn loop_wraps_are_reported() {
+    let (mut player, mut sound, block_frames) = load();
+    let (loop_start, loop_end) = (1000, 3000);
+    let length = loop_end - loop_start;
+    let start = 2500;
+    let (listener, events) = recorder();
+    let _playback = player
+        .play_loop(&mut sound, loop_start, loop_end, start, listener)
+        .unwrap();
+
+    advance(&mut player, 2);
+
+    let first = loop_start + (start + block_frames - loop_start) % length;
+    let second = loop_start + (first + block_frames - loop_start) % length;
+    let mut expected = vec![Event::Started];
+    for (previous, frame) in [(start, first), (first, second)] {
+        if frame < previous {
+            expected.push(Event::Looped);
+        }
+        expected.push(Event::Progress(frame));
+    }
+    assert_eq!(*events.lock().unwrap(), expected);
+}
+
+#[test]
+fn playback_rate_scales_advance() {
+    let (mut player, mut sound, block_frames) = load();
+    let mut playback = player.play_from(&mut sound, 0, None).unwrap();
+
+    player
+        .set_playback_rate(&mut playback, 0.5, RateMode::Varispeed)
+        .unwrap();
+    advance(&mut player, 2);
+    assert_eq!(player.pause(&mut playback).unwrap(), block_frames);
+}
+
+#[test]
+fn dsp_buffer_length_sets_block() {
+    let (mut player, mut sound, block_frames) =
+        load_with(FmodConfig::new().with_dsp_buffer(256, 4));
+    assert_eq!(block_frames, 256);
+    let mut playback = player.play_from(&mut sound, 0, None).unwrap();
+    advance(&mut player, 3);
+    assert_eq!(player.pause(&mut playback).unwrap(), 768);
+}
+
+#[test]
+fn max_channels_allows_overlapping_playbacks() {
+    let (mut player, mut sound, _) = load_with(FmodConfig::new().with_max_channels(4));
+    let mut playbacks: Vec<_> = (0..4)
+        .map(|i| player.play_from(&mut sound, i * 1000, None).unwrap())
+        .collect();
+    advance(&mut player, 1);
+    for playback in &mut playbacks {
+        assert_eq!(player.get_state(playback).unwrap(), PlaybackState::Playing);
+    }
+
+    // With the default two channels, a third playback steals one of the others
+    let (mut player, mut sound, _) = load();
+    let mut playbacks: Vec<_> = (0..3)
+        .map(|i| player.play_from(&mut sound, i * 1000, None).unwrap())
+        .collect();
+    advance(&mut player, 1);
+    let mut playing = 0;
+    for playback in &mut playbacks {
+        if player.get_state(playback).unwrap() == PlaybackState::Playing {
+            playing += 1;
+        }
+    }
+    assert_eq!(playing, 2);
+}
+
+#[test]
+fn rejects_ranges_outside_sound() {
+    let (mut player, mut sound, _) = load();
+    let length = SAMPLE_RATE as u64;
+    for (start, end) in [(2000, 1000), (0, length + 1), (length + 1, length + 2)] {
+        let error = player
+            .play_range(&mut sound, start, end, None)
+            .err()
+            .unwrap();
+        assert_eq!(error.kind, PlayerErrorKind::InvalidRange);
+    }
+    let error = player
+        .play_loop(&mut sound, 1000, length + 1, 1000, None)
+        .err()
+        .unwrap();
+    assert_eq!(error.kind, PlayerErrorKind::InvalidRange);
+
+    // No failed attempt is left holding one of the two channels
+    let mut playbacks: Vec<_> = (0..2)
+        .map(|_| player.play_from(&mut sound, 0, None).unwrap())
+        .collect();
+    advance(&mut player, 1);
+    for playback in &mut playbacks {
+        assert_eq!(player.get_state(playback).unwrap(), PlaybackState::Playing);
+    }
+}
+
+#[test]
+fn overlapping_stream_playbacks_each_play() {
+    let (mut player, _, _) = load();
+    let path = std::env::temp_dir().join("driftwave-nrt-stream.wav");
+    std::fs::write(&path, wav()).unwrap();
+    let mut sound = block(player.load_stream(path.to_str().unwrap())).unwrap();
+
+    // A failed attempt leaves the stream with the playback holding it
+    let mut first = player.play_from(&mut sound, 0, None).unwrap();
+    assert!(player.play_range(&mut sound, 2000, 1000, None).is_err());
+    let mut second = player.play_from(&mut sound, 1000, None).unwrap();
+    advance(&mut player, 2);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        player.get_state(&mut first).unwrap(),
+        PlaybackState::Playing
+    );
+    assert_eq!(
+        player.get_state(&mut second).unwrap(),
+        PlaybackState::Playing
+    );
+}
+
+#[test]
+fn owned_bytes_play_to_completion() {
+    let (mut player, _, block_frames) = load();
+    // The sound holds the only copy of the bytes, which FMOD reads in place
+    let mut sound = block(player.load_owned(wav())).unwrap();
+    let (start, end) = (500, 500 + 2 * block_frames + 300);
+    let (listener, events) = recorder();
+    let mut playback = player.play_range(&mut sound, start, end, listener).unwrap();
+
+    advance(&mut player, 2);
+    assert_eq!(
+        player.get_state(&mut playback).unwrap(),
+        PlaybackState::Playing
+    );
+    advance(&mut player, 1);
+    assert_eq!(
+        player.get_state(&mut playback).unwrap(),
+        PlaybackState::Finished
+    );
+
+    advance(&mut player, 4);
+    let events = events.lock().unwrap();
+    let progress: Vec<u64> = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Progress(frame) => Some(*frame),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        progress[..3],
+        [start + block_frames, start + 2 * block_frames, end]
+    );
+    assert!(progress[3..].iter().all(|&frame| frame == end));
+    assert_eq!(events.first(), Some(&Event::Started));
+    assert_eq!(events.last(), Some(&Event::Finished));
+}
+
+#[test]
+fn resume_after_finish_fails() {
+    let (mut player, mut sound, block_frames) = load();
+    let mut playback = player
+        .play_range(&mut sound, 0, block_frames, None)
+        .unwrap();
+    advance(&mut player, 6);
+    assert_eq!(
+        player.get_state(&mut playback).unwrap(),
+        PlaybackState::Finished
+    );
+    let error = player.resume(&mut playback).err().unwrap();
+    assert_eq!(error.kind, PlayerErrorKind::InvalidState);
+}
+
+#[test]
+fn rejects_path_with_nul() {
+    let (mut player, _, _) = load();
+    for error in [
+        block(player.load("tone\0.wav")).err().unwrap(),
+        block(player.load_stream("tone\0.wav")).err().unwrap(),
+    ] {
+        assert_eq!(error.kind, PlayerErrorKind::InvalidArgument);
+    }
+}
+
+#[test]
+fn reinit_replaces_idle_system_and_failed_init_leaves_none() {
+    let mut player = FmodPlayer::new();
+    let nrt = || FmodConfig::new().with_output(FmodOutput::NoSoundNrt);
+    player.init_with_config(nrt()).unwrap();
+    player
+        .init_with_config(nrt().with_dsp_buffer(256, 4))
+        .unwrap();
+    assert_eq!(player.block_frames().unwrap(), 256);
+
+    let error = player
+        .init_with_config(FmodConfig::new().with_output(FmodOutput::WavWriterNrt("a\0b".into())))
+        .unwrap_err();
+    assert_eq!(error.kind, PlayerErrorKind::InvalidArgument);
+    let error = player.block_frames().unwrap_err();
+    assert_eq!(error.kind, PlayerErrorKind::NotInitialized);
+
+    player.init_with_config(nrt().with_driver(99)).unwrap_err();
+    let error = player.block_frames().unwrap_err();
+    assert_eq!(error.kind, PlayerErrorKind::NotInitialized);
+
+    player.init_with_config(nrt()).unwrap();
+    let mut sound = block(player.load_bytes(&wav())).unwrap();
+    let mut playback = player.play_from(&mut sound, 0, None).unwrap();
+
+    // The system outlives everything made on it
+    let error = player.init_with_config(nrt()).unwrap_err();
+    assert_eq!(error.kind, PlayerErrorKind::InvalidState);
+    advance(&mut player, 1);
+    assert_eq!(
+        player.get_state(&mut playback).unwrap(),
+        PlaybackState::Playing
+    );
+    drop(playback);
+    player.init_with_config(nrt()).unwrap_err();
+    drop(sound);
+    player.init_with_config(nrt()).unwrap();
+}
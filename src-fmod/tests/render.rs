@@ -0,0 +1,209 @@
+//! Offline renders compared against golden files in `tests/golden`. After an
+//! intended change to the playback path, regenerate them with
+//! `DRIFTWAVE_BLESS=1 cargo test -p driftwave-fmod --test render`.
+
+use driftwave_core::decode;
+use driftwave_core::{Player, PlayerError, PlayerErrorKind, RateMode};
+use driftwave_fmod::{FmodConfig, FmodOutput, FmodPlayback, FmodPlayer, FmodSound};
+
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::io::Cursor;
+use std::path::Path;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+
+const SAMPLE_RATE: u32 = 48000;
+
+// The FMOD player's futures never wait, so a single poll completes them
+fn block<F: Future>(future: F) -> F::Output {
+    match pin!(future).poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(output) => output,
+        Poll::Pending => panic!("future did not complete"),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Load {
+    Bytes,
+    File,
+    Stream,
+    Decoder,
+}
+
+// Half a second of a 16-bit mono chirp
+fn source() -> Vec<u8> {
+    let frames = SAMPLE_RATE / 2;
+    let data = frames * 2;
+    let mut bytes = Vec::new();
+    bytes.extend(b"RIFF");
+    bytes.extend((36 + data).to_le_bytes());
+    bytes.extend(b"WAVEfmt ");
+    bytes.extend(16u32.to_le_bytes());
+    bytes.extend(1u16.to_le_bytes()); // PCM
+    bytes.extend(1u16.to_le_bytes()); // mono
+    bytes.extend(SAMPLE_RATE.to_le_bytes());
+    bytes.extend((SAMPLE_RATE * 2).to_le_bytes());
+    bytes.extend(2u16.to_le_bytes());
+    bytes.extend(16u16.to_le_bytes());
+    bytes.extend(b"data");
+    bytes.extend(data.to_le_bytes());
+    for i in 0..frames {
+        let t = i as f32 / SAMPLE_RATE as f32;
+        let sample = ((220.0 + 880.0 * t) * t * std::f32::consts::TAU).sin() * 16000.0;
+        bytes.extend((sample as i16).to_le_bytes());
+    }
+    bytes
+}
+
+fn player() -> FmodPlayer {
+    let mut player = FmodPlayer::new();
+    player
+        .init_with_config(FmodConfig::new().with_output(FmodOutput::NoSoundNrt))
+        .unwrap();
+    player
+}
+
+// Loads the source as `load` says; files are written under `name`
+fn load(player: &mut FmodPlayer, name: &str, load: Load) -> FmodSound {
+    let path = env::temp_dir().join(format!("driftwave-render-{}-{:?}.wav", name, load));
+    let path = path.to_str().unwrap();
+    let sound = match load {
+        Load::Bytes => block(player.load_bytes(&source())),
+        Load::File | Load::Stream => {
+            fs::write(path, source()).unwrap();
+            let sound = match load {
+                Load::File => block(player.load(path)),
+                _ => block(player.load_stream(path)),
+            };
+            // A stream reopens the file for the render
+            if matches!(load, Load::File) {
+                fs::remove_file(path).unwrap();
+            }
+            sound
+        }
+        Load::Decoder => {
+            let decoder = decode::open(Cursor::new(source())).unwrap();
+            player.load_decoder(decoder)
+        }
+    };
+    sound.unwrap()
+}
+
+// Renders `start..end` of the source as configured and checks it against `golden/<name>.wav`
+fn check(
+    name: &str,
+    start: u64,
+    end: u64,
+    configure: impl FnOnce(&mut FmodPlayer, &mut FmodPlayback) -> Result<(), PlayerError>,
+) {
+    check_loaded(name, Load::Bytes, start, end, configure);
+}
+
+fn check_loaded(
+    name: &str,
+    from: Load,
+    start: u64,
+    end: u64,
+    configure: impl FnOnce(&mut FmodPlayer, &mut FmodPlayback) -> Result<(), PlayerError>,
+) {
+    let mut player = player();
+    let sound = load(&mut player, name, from);
+    let output = env::temp_dir().join(format!("driftwave-render-{}-{:?}-out.wav", name, from));
+    block(player.render(&sound, start, end, &output, configure)).unwrap();
+    drop(sound);
+    if matches!(from, Load::Stream) {
+        let path = env::temp_dir().join(format!("driftwave-render-{}-{:?}.wav", name, from));
+        fs::remove_file(path).unwrap();
+    }
+    let rendered = fs::read(&output).unwrap();
+    fs::remove_file(&output).unwrap();
+
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.wav", name));
+    if env::var_os("DRIFTWAVE_BLESS").is_some() && matches!(from, Load::Bytes) {
+        fs::write(&golden, &rendered).unwrap();
+        return;
+    }
+    let expected = fs::read(&golden).unwrap();
+
+    // FMOD fades a sample in over its first 64 frames, but starts a stream at
+    // full level, so streamed kinds only match the golden after the ramp
+    let ramp = match from {
+        Load::Stream | Load::Decoder => 64 * 2,
+        Load::Bytes | Load::File => 0,
+    };
+
+    // Same header, and samples within a step of rounding
+    let (header, samples) = rendered.split_at(data_offset(&rendered));
+    let (expected_header, expected_samples) = expected.split_at(data_offset(&expected));
+    assert_eq!(header, expected_header, "{} header differs", name);
+    let samples = samples
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]));
+    let expected_samples = expected_samples
+        .chunks_exact(2)
+        .map(|pair| i16::from_le_bytes([pair[0], pair[1]]));
+    for (i, (sample, expected)) in samples.zip(expected_samples).enumerate().skip(ramp) {
+        assert!(
+            (sample as i32 - expected as i32).abs() <= 1,
+            "{} sample {} is {}, expected {}",
+            name,
+            i,
+            sample,
+            expected
+        );
+    }
+}
+
+// Start of the 16-bit samples, after the header
+fn data_offset(wav: &[u8]) -> usize {
+    wav.windows(4).position(|id| id == b"data").unwrap() + 8
+}
+
+#[test]
+fn renders_range() {
+    check("range", 1000, 5800, |_, _| Ok(()));
+}
+
+#[test]
+fn renders_every_kind_of_sound() {
+    for from in [Load::File, Load::Stream, Load::Decoder] {
+        check_loaded("range", from, 1000, 5800, |_, _| Ok(()));
+    }
+}
+
+#[test]
+fn refuses_decoded_sound_while_playing() {
+    let mut player = player();
+    let mut sound = load(&mut player, "busy", Load::Decoder);
+    let output = env::temp_dir().join("driftwave-render-busy-out.wav");
+    let playback = player.play_from(&mut sound, 0, None).unwrap();
+    let error = block(player.render(&sound, 0, 4800, &output, |_, _| Ok(()))).unwrap_err();
+    assert_eq!(error.kind, PlayerErrorKind::InvalidState);
+    drop(playback);
+}
+
+#[test]
+fn renders_volume_and_pan() {
+    check("volume_pan", 0, 4800, |player, playback| {
+        player.set_volume(playback, 0.5)?;
+        player.set_pan(playback, -0.5)
+    });
+}
+
+#[test]
+fn renders_varispeed() {
+    check("varispeed", 2400, 7200, |player, playback| {
+        player.set_playback_rate(playback, 1.5, RateMode::Varispeed)
+    });
+}
+
+#[test]
+fn renders_pitch_preserving_rate() {
+    check("preserve_pitch", 2400, 7200, |player, playback| {
+        player.set_playback_rate(playback, 0.75, RateMode::PreservePitch)
+    });
+}
@@ -1,16 +1,108 @@
 mod player;
 
 use player::WebPlayer;
-use driftwave_core::Player;
+use driftwave_core::{
+    db_to_linear, PlaybackListener, PlaybackState, Player, PlayerError, PlayerErrorKind, RateMode,
+};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen_futures::future_to_promise;
 use js_sys::Promise;
 
+// Converts to a JS Error named after the error kind, e.g. "FileNotFound",
+// with the backend's code as `code` for "BackendSpecific"
+fn js_error(error: &PlayerError) -> JsValue {
+    let name = match error.kind {
+        PlayerErrorKind::NotInitialized => "NotInitialized",
+        PlayerErrorKind::FileNotFound => "FileNotFound",
+        PlayerErrorKind::UnsupportedFormat => "UnsupportedFormat",
+        PlayerErrorKind::DecodeFailed => "DecodeFailed",
+        PlayerErrorKind::InvalidRange => "InvalidRange",
+        PlayerErrorKind::InvalidArgument => "InvalidArgument",
+        PlayerErrorKind::InvalidState => "InvalidState",
+        PlayerErrorKind::DeviceUnavailable => "DeviceUnavailable",
+        PlayerErrorKind::BackendSpecific { .. } => "BackendSpecific",
+    };
+    let js_error = js_sys::Error::new(&error.message);
+    js_error.set_name(name);
+    if let PlayerErrorKind::BackendSpecific { code } = error.kind {
+        let _ = js_sys::Reflect::set(&js_error, &"code".into(), &code.into());
+    }
+    js_error.into()
+}
+
+// Forwards playback events to a JS `(event, value) => void` callback
+struct JsListener {
+    callback: js_sys::Function,
+}
+
+// wasm32 without threads runs everything, listener included, on the main thread
+unsafe impl Send for JsListener {}
+
+impl JsListener {
+    fn emit(&self, event: &str, value: JsValue) {
+        let _ = self.callback.call2(&JsValue::NULL, &JsValue::from_str(event), &value);
+    }
+}
+
+impl PlaybackListener for JsListener {
+    fn on_progress(&mut self, position_frames: u64) {
+        self.emit("timeupdate", JsValue::from_f64(position_frames as f64));
+    }
+
+    fn on_finished(&mut self) {
+        self.emit("finish", JsValue::UNDEFINED);
+    }
+
+    fn on_looped(&mut self) {
+        self.emit("loop", JsValue::UNDEFINED);
+    }
+
+    fn on_error(&mut self, error: &PlayerError) {
+        self.emit("error", js_error(error));
+    }
+}
+
 #[wasm_bindgen]
 pub struct Driftwave {
     player: WebPlayer,
     current_sound: Option<player::WebSound>,
     current_playback: Option<player::WebPlayback>,
+    listener: Option<js_sys::Function>,
+    volume: f32,
+    muted: bool,
+    pan: f32,
+    rate: f64,
+    rate_mode: RateMode,
+}
+
+impl Driftwave {
+    fn playback_listener(&self) -> Option<Box<dyn PlaybackListener>> {
+        self.listener.clone().map(|callback| {
+            Box::new(JsListener { callback }) as Box<dyn PlaybackListener>
+        })
+    }
+
+    // Makes `playback` current, carrying over volume, mute, pan and rate
+    fn set_playback(&mut self, mut playback: player::WebPlayback) -> Result<(), JsValue> {
+        if self.volume != 1.0 {
+            self.player.set_volume(&mut playback, self.volume)
+                .map_err(|e| js_error(&e))?;
+        }
+        if self.muted {
+            self.player.set_muted(&mut playback, true)
+                .map_err(|e| js_error(&e))?;
+        }
+        if self.pan != 0.0 {
+            self.player.set_pan(&mut playback, self.pan)
+                .map_err(|e| js_error(&e))?;
+        }
+        if self.rate != 1.0 {
+            self.player.set_playback_rate(&mut playback, self.rate, self.rate_mode)
+                .map_err(|e| js_error(&e))?;
+        }
+        self.current_playback = Some(playback);
+        Ok(())
+    }
 }
 
 #[wasm_bindgen]
@@ -19,21 +111,32 @@ impl Driftwave {
     pub fn new() -> Result<Driftwave, JsValue> {
         console_error_panic_hook::set_once();
         let mut player = WebPlayer::new()?;
-        player.init().map_err(|e| JsValue::from_str(&e.message))?;
+        player.init().map_err(|e| js_error(&e))?;
 
         Ok(Driftwave {
             player,
             current_sound: None,
             current_playback: None,
+            listener: None,
+            volume: 1.0,
+            muted: false,
+            pan: 0.0,
+            rate: 1.0,
+            rate_mode: RateMode::Varispeed,
         })
     }
 
+    /// Registers `callback(event, value)` for events raised during playback.
+    pub fn set_listener(&mut self, callback: js_sys::Function) {
+        self.listener = Some(callback);
+    }
+
     pub fn load(&mut self, url: String) -> Promise {
         let mut player = WebPlayer::new().unwrap();
 
         future_to_promise(async move {
             let sound = player.load(&url).await
-                .map_err(|e| JsValue::from_str(&e.message))?;
+                .map_err(|e| js_error(&e))?;
 
             Ok(JsValue::from_str("loaded"))
         })
@@ -41,34 +144,55 @@ impl Driftwave {
 
     pub async fn load_async(&mut self, url: String) -> Result<(), JsValue> {
         let sound = self.player.load(&url).await
-            .map_err(|e| JsValue::from_str(&e.message))?;
+            .map_err(|e| js_error(&e))?;
+        self.current_sound = Some(sound);
+        Ok(())
+    }
+
+    /// Loads an encoded audio file from `buffer`, detaching it.
+    pub async fn load_array_buffer(&mut self, buffer: js_sys::ArrayBuffer) -> Result<(), JsValue> {
+        let sound = self.player.load_array_buffer(buffer).await
+            .map_err(|e| js_error(&e))?;
         self.current_sound = Some(sound);
         Ok(())
     }
 
     pub fn play(&mut self) -> Result<(), JsValue> {
+        let listener = self.playback_listener();
         if let Some(ref mut sound) = self.current_sound {
-            let playback = self.player.play_from(sound, 0, None)
-                .map_err(|e| JsValue::from_str(&e.message))?;
-            self.current_playback = Some(playback);
+            let playback = self.player.play_from(sound, 0, listener)
+                .map_err(|e| js_error(&e))?;
+            return self.set_playback(playback);
         }
         Ok(())
     }
 
     pub fn play_from(&mut self, start_frame: u32) -> Result<(), JsValue> {
+        let listener = self.playback_listener();
         if let Some(ref mut sound) = self.current_sound {
-            let playback = self.player.play_from(sound, start_frame as u64, None)
-                .map_err(|e| JsValue::from_str(&e.message))?;
-            self.current_playback = Some(playback);
+            let playback = self.player.play_from(sound, start_frame as u64, listener)
+                .map_err(|e| js_error(&e))?;
+            return self.set_playback(playback);
         }
         Ok(())
     }
 
     pub fn play_range(&mut self, start_frame: u32, end_frame: u32) -> Result<(), JsValue> {
+        let listener = self.playback_listener();
+        if let Some(ref mut sound) = self.current_sound {
+            let playback = self.player.play_range(sound, start_frame as u64, end_frame as u64, listener)
+                .map_err(|e| js_error(&e))?;
+            return self.set_playback(playback);
+        }
+        Ok(())
+    }
+
+    pub fn play_loop(&mut self, loop_start: u32, loop_end: u32, start_frame: u32) -> Result<(), JsValue> {
+        let listener = self.playback_listener();
         if let Some(ref mut sound) = self.current_sound {
-            let playback = self.player.play_range(sound, start_frame as u64, end_frame as u64, None)
-                .map_err(|e| JsValue::from_str(&e.message))?;
-            self.current_playback = Some(playback);
+            let playback = self.player.play_loop(sound, loop_start as u64, loop_end as u64, start_frame as u64, listener)
+                .map_err(|e| js_error(&e))?;
+            return self.set_playback(playback);
         }
         Ok(())
     }
@@ -76,26 +200,103 @@ impl Driftwave {
     pub fn pause(&mut self) -> Result<u32, JsValue> {
         if let Some(ref mut playback) = self.current_playback {
             let frame = self.player.pause(playback)
-                .map_err(|e| JsValue::from_str(&e.message))?;
+                .map_err(|e| js_error(&e))?;
             Ok(frame as u32)
         } else {
             Ok(0)
         }
     }
 
+    pub fn resume(&mut self) -> Result<(), JsValue> {
+        if let Some(ref mut playback) = self.current_playback {
+            self.player.resume(playback)
+                .map_err(|e| js_error(&e))?;
+        }
+        Ok(())
+    }
+
+    pub fn seek(&mut self, frame: u32) -> Result<(), JsValue> {
+        if let Some(ref mut playback) = self.current_playback {
+            self.player.seek(playback, frame as u64)
+                .map_err(|e| js_error(&e))?;
+        }
+        Ok(())
+    }
+
+    pub fn set_volume(&mut self, volume: f32) -> Result<(), JsValue> {
+        if let Some(ref mut playback) = self.current_playback {
+            self.player.set_volume(playback, volume)
+                .map_err(|e| js_error(&e))?;
+        }
+        self.volume = volume;
+        Ok(())
+    }
+
+    pub fn set_volume_db(&mut self, db: f32) -> Result<(), JsValue> {
+        self.set_volume(db_to_linear(db))
+    }
+
+    pub fn set_muted(&mut self, muted: bool) -> Result<(), JsValue> {
+        if let Some(ref mut playback) = self.current_playback {
+            self.player.set_muted(playback, muted)
+                .map_err(|e| js_error(&e))?;
+        }
+        self.muted = muted;
+        Ok(())
+    }
+
+    pub fn set_pan(&mut self, pan: f32) -> Result<(), JsValue> {
+        if let Some(ref mut playback) = self.current_playback {
+            self.player.set_pan(playback, pan)
+                .map_err(|e| js_error(&e))?;
+        }
+        self.pan = pan;
+        Ok(())
+    }
+
+    /// Plays at `rate` times normal speed; with `preserve_pitch` the audio is
+    /// time-stretched instead of sped up like tape.
+    pub fn set_playback_rate(&mut self, rate: f64, preserve_pitch: bool) -> Result<(), JsValue> {
+        let mode = if preserve_pitch { RateMode::PreservePitch } else { RateMode::Varispeed };
+        if let Some(ref mut playback) = self.current_playback {
+            self.player.set_playback_rate(playback, rate, mode)
+                .map_err(|e| js_error(&e))?;
+        }
+        self.rate = rate;
+        self.rate_mode = mode;
+        Ok(())
+    }
+
     pub fn is_playing(&mut self) -> Result<bool, JsValue> {
         if let Some(ref mut playback) = self.current_playback {
             self.player.is_playing(playback)
-                .map_err(|e| JsValue::from_str(&e.message))
+                .map_err(|e| js_error(&e))
         } else {
             Ok(false)
         }
     }
 
+    pub fn get_state(&mut self) -> Result<String, JsValue> {
+        let state = if let Some(ref mut playback) = self.current_playback {
+            self.player.get_state(playback)
+                .map_err(|e| js_error(&e))?
+        } else {
+            PlaybackState::Invalid
+        };
+        let name = match state {
+            PlaybackState::Playing => "playing",
+            PlaybackState::Paused => "paused",
+            PlaybackState::Finished => "finished",
+            PlaybackState::Stopped => "stopped",
+            PlaybackState::Invalid => "invalid",
+        };
+        Ok(name.to_string())
+    }
+
     pub fn get_metadata(&mut self) -> Result<JsValue, JsValue> {
         if let Some(ref mut sound) = self.current_sound {
             let metadata = self.player.get_metadata(sound)
-                .map_err(|e| JsValue::from_str(&e.message))?;
+                .map_err(|e| js_error(&e))?;
 
             let obj = js_sys::Object::new();
             js_sys::Reflect::set(&obj, &"sampleRate".into(), &metadata.sample_rate.into())?;
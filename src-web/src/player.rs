@@ -1,9 +1,57 @@
 use async_trait::async_trait;
-use driftwave_core::{Metadata, PlaybackState, Player, PlayerError, PlaybackListener};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::{Rc, Weak};
+use driftwave_core::{
+    Metadata, PlaybackState, Player, PlayerError, PlayerErrorKind, PlaybackListener, RateMode,
+    Stretch,
+};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{AudioBuffer, AudioBufferSourceNode, AudioContext, Request, Response};
+use web_sys::{
+    AudioBuffer, AudioBufferSourceNode, AudioContext, AudioContextState, AudioParam, DomException,
+    GainNode, Request, Response, StereoPannerNode,
+};
+
+// Time constant for volume and pan changes, short enough to feel immediate
+// but long enough to avoid zipper noise
+const RAMP_SECONDS: f64 = 0.01;
+
+// Source audio stretched at a time for pitch-preserving playback. The block
+// after the one playing is always queued, so stretching keeps a block ahead.
+const STRETCH_BLOCK_SECONDS: f64 = 2.0;
+
+// Classifies a failed Web API call by the name of the DOMException or JS error it threw
+fn web_error(error: JsValue, context: &str) -> PlayerError {
+    if let Some(exception) = error.dyn_ref::<DomException>() {
+        let kind = match exception.name().as_str() {
+            "NotFoundError" => PlayerErrorKind::FileNotFound,
+            "NotSupportedError" => PlayerErrorKind::UnsupportedFormat,
+            // decodeAudioData's rejection for data it cannot decode
+            "EncodingError" => PlayerErrorKind::DecodeFailed,
+            "IndexSizeError" => PlayerErrorKind::InvalidRange,
+            // Autoplay policy or a missing output device
+            "NotAllowedError" | "NotReadableError" => PlayerErrorKind::DeviceUnavailable,
+            _ => PlayerErrorKind::BackendSpecific { code: exception.code() as i32 },
+        };
+        return PlayerError {
+            kind,
+            message: format!("{}: {}: {}", context, exception.name(), exception.message()),
+        };
+    }
+    let range_error = error
+        .dyn_ref::<js_sys::Error>()
+        .is_some_and(|e| e.name() == "RangeError");
+    PlayerError {
+        kind: if range_error {
+            PlayerErrorKind::InvalidRange
+        } else {
+            PlayerErrorKind::BackendSpecific { code: 0 }
+        },
+        message: format!("{}: {:?}", context, error),
+    }
+}
 
 pub struct WebPlayer {
     context: AudioContext,
@@ -26,6 +74,18 @@ pub struct WebPlayback {
     channels: u32,
     frame_count: u32,
     end_frame: Option<u64>,                 // Optional end frame for range playback
+    loop_range: Option<(u64, u64)>,         // Loop start and end for looping playback
+    gain: GainNode,                         // Source -> gain -> panner -> destination
+    panner: StereoPannerNode,
+    volume: f32,
+    muted: bool,
+    rate: f64,
+    rate_mode: RateMode,
+    stretched: Option<Rc<RefCell<StretchQueue>>>, // Replaces source for pitch-preserving rates
+    listener: Option<Rc<RefCell<Box<dyn PlaybackListener>>>>,
+    ended: Rc<Cell<bool>>,                  // Set by the source's onended handler
+    onended: Option<Closure<dyn FnMut()>>,
+    ticking: Option<Rc<Cell<bool>>>,        // Keeps the current progress loop alive
 }
 
 impl WebPlayer {
@@ -44,80 +104,465 @@ impl Drop for WebPlayer {
 impl Drop for WebPlayback {
     fn drop(&mut self) {
         // Try to stop if still playing
-        if let Some(ref source) = self.source {
+        let _ = self.stop_source();
+        let _ = self.panner.disconnect();
+    }
+}
+
+impl WebPlayback {
+    fn notify(&self, event: impl FnOnce(&mut dyn PlaybackListener)) {
+        if let Some(listener) = &self.listener
+            && let Ok(mut listener) = listener.try_borrow_mut()
+        {
+            event(listener.as_mut());
+        }
+    }
+
+    // Whether a source or stretched blocks are playing, or have played out, since the last start
+    fn started(&self) -> bool {
+        self.source.is_some() || self.stretched.is_some()
+    }
+
+    fn clock(&self) -> SourceClock {
+        SourceClock {
+            start_time: self.start_time,
+            start_frame: self.start_frame,
+            stop_frame: self.end_frame.unwrap_or(self.frame_count as u64),
+            sample_rate: self.sample_rate,
+            rate: self.rate,
+            loop_range: self.loop_range,
+        }
+    }
+
+    // Stops the current source without reporting it as ended
+    fn stop_source(&mut self) -> Result<(), PlayerError> {
+        if let Some(ticking) = self.ticking.take() {
+            ticking.set(false);
+        }
+        if let Some(source) = self.source.take() {
+            #[allow(deprecated)]
+            source.set_onended(None);
             #[allow(deprecated)]
-            let _ = source.stop();
+            source.stop().map_err(|e| web_error(e, "Failed to stop playback"))?;
+        }
+        if let Some(stretched) = self.stretched.take() {
+            stretched.borrow_mut().stop()?;
         }
+        self.onended = None;
+        Ok(())
+    }
+}
+
+// Pitch-preserving playback, stretched from the buffer a block at a time just
+// ahead of the playhead and queued back to back on the context's clock
+struct StretchQueue {
+    buffer: AudioBuffer,
+    destination: GainNode,
+    stretch: Stretch,
+    rate: f64,
+    stop_frame: u64,
+    loop_range: Option<(u64, u64)>,
+    next_time: f64,                         // Context time the next block starts at
+    blocks: VecDeque<(AudioBufferSourceNode, Closure<dyn FnMut()>)>, // Playing block first
+    finish: Rc<dyn Fn()>,
+    listener: Option<Rc<RefCell<Box<dyn PlaybackListener>>>>,
+}
+
+impl StretchQueue {
+    // Starts `playback` at `frame` and context time `when`; `finish` runs once the last block ends
+    fn start(
+        playback: &WebPlayback,
+        frame: u64,
+        when: f64,
+        finish: Rc<dyn Fn()>,
+    ) -> Result<Rc<RefCell<StretchQueue>>, PlayerError> {
+        let queue = Rc::new(RefCell::new(StretchQueue {
+            buffer: playback.buffer.clone(),
+            destination: playback.gain.clone(),
+            stretch: Stretch::new(
+                playback.channels as usize,
+                playback.sample_rate as u32,
+                playback.rate,
+                frame,
+            ),
+            rate: playback.rate,
+            stop_frame: playback.end_frame.unwrap_or(playback.frame_count as u64),
+            loop_range: playback.loop_range,
+            next_time: when,
+            blocks: VecDeque::new(),
+            finish,
+            listener: playback.listener.clone(),
+        }));
+        StretchQueue::queue_block(&queue)?;
+        StretchQueue::queue_block(&queue)?;
+        Ok(queue)
+    }
+
+    // Stretches the next block and schedules it to start as the last queued one ends
+    fn queue_block(queue: &Rc<RefCell<StretchQueue>>) -> Result<(), PlayerError> {
+        let mut this = queue.borrow_mut();
+        let sample_rate = this.buffer.sample_rate();
+        let channels = this.buffer.number_of_channels();
+        let end = this.loop_range.map_or(this.stop_frame, |(_, loop_end)| loop_end);
+        let mut position = this.stretch.source_position();
+        if position >= end {
+            // Each pass through a loop is a fresh run from its start
+            let Some((loop_start, _)) = this.loop_range else {
+                return Ok(());
+            };
+            this.stretch = Stretch::new(channels as usize, sample_rate as u32, this.rate, loop_start);
+            position = loop_start;
+        }
+        let block_end = (position + (STRETCH_BLOCK_SECONDS * sample_rate as f64) as u64).min(end);
+        let frames = this.stretch.frames_until(block_end).max(1);
+
+        // Only the stretch's neighbourhood of the buffer is copied out
+        let (input_start, input_end) = this.stretch.input_range(frames);
+        let input_end = input_end.min(this.buffer.length() as u64).max(input_start);
+        let mut input = vec![vec![0.0f32; (input_end - input_start) as usize]; channels as usize];
+        for (channel, data) in input.iter_mut().enumerate() {
+            if !data.is_empty() {
+                this.buffer
+                    .copy_from_channel_with_start_in_channel(data, channel as i32, input_start as u32)
+                    .map_err(|e| web_error(e, "Failed to read channel data"))?;
+            }
+        }
+        let output = this.stretch.render(&input, input_start, frames);
+
+        let context = this.destination.context();
+        let block = context
+            .create_buffer(channels, frames as u32, sample_rate)
+            .map_err(|e| web_error(e, "Failed to create buffer"))?;
+        for (channel, data) in output.iter().enumerate() {
+            block
+                .copy_to_channel(data, channel as i32)
+                .map_err(|e| web_error(e, "Failed to write channel data"))?;
+        }
+        let source = context
+            .create_buffer_source()
+            .map_err(|e| web_error(e, "Failed to create buffer source"))?;
+        source.set_buffer(Some(&block));
+        source
+            .connect_with_audio_node(&this.destination)
+            .map_err(|e| web_error(e, "Failed to connect to gain"))?;
+        source
+            .start_with_when(this.next_time)
+            .map_err(|e| web_error(e, "Failed to start playback"))?;
+
+        let queue = Rc::downgrade(queue);
+        let onended = Closure::<dyn FnMut()>::new(move || StretchQueue::block_ended(&queue));
+        #[allow(deprecated)]
+        source.set_onended(Some(onended.as_ref().unchecked_ref()));
+        this.next_time += frames as f64 / sample_rate as f64;
+        this.blocks.push_back((source, onended));
+        Ok(())
+    }
+
+    // Moves on past the block that just ended, finishing after the last
+    fn block_ended(queue: &Weak<RefCell<StretchQueue>>) {
+        let Some(queue) = queue.upgrade() else {
+            return;
+        };
+        let finish = {
+            let mut this = queue.borrow_mut();
+            this.blocks.pop_front();
+            this.blocks.is_empty().then(|| this.finish.clone())
+        };
+        if let Some(finish) = finish {
+            finish();
+        } else if let Err(error) = StretchQueue::queue_block(&queue) {
+            let listener = queue.borrow().listener.clone();
+            if let Some(listener) = listener
+                && let Ok(mut listener) = listener.try_borrow_mut()
+            {
+                listener.on_error(&error);
+            }
+        }
+    }
+
+    // Stops every queued block without reporting the end
+    fn stop(&mut self) -> Result<(), PlayerError> {
+        for (source, _) in self.blocks.drain(..) {
+            #[allow(deprecated)]
+            source.set_onended(None);
+            #[allow(deprecated)]
+            source.stop().map_err(|e| web_error(e, "Failed to stop playback"))?;
+        }
+        Ok(())
     }
 }
 
 impl WebPlayer {
     fn create_and_start_source(
         &mut self,
-        buffer: &AudioBuffer,
+        playback: &WebPlayback,
         start_frame: u64,
-        end_frame: Option<u64>,
-        sample_rate: f32,
     ) -> Result<AudioBufferSourceNode, PlayerError> {
+        let sample_rate = playback.sample_rate as f64;
         let source = self
             .context
             .create_buffer_source()
-            .map_err(|e| PlayerError {
-                message: format!("Failed to create buffer source: {:?}", e),
-            })?;
+            .map_err(|e| web_error(e, "Failed to create buffer source"))?;
 
-        source.set_buffer(Some(buffer));
+        source.set_buffer(Some(&playback.buffer));
+        if playback.rate_mode == RateMode::Varispeed {
+            source.playback_rate().set_value(playback.rate as f32);
+        }
         source
-            .connect_with_audio_node(&self.context.destination())
-            .map_err(|e| PlayerError {
-                message: format!("Failed to connect to destination: {:?}", e),
-            })?;
+            .connect_with_audio_node(&playback.gain)
+            .map_err(|e| web_error(e, "Failed to connect to gain"))?;
+
+        let seconds = |frame: u64| frame as f64 / sample_rate;
+        let start_time = seconds(start_frame);
 
-        let start_time = start_frame as f64 / sample_rate as f64;
+        if let Some((loop_start, loop_end)) = playback.loop_range {
+            source.set_loop(true);
+            source.set_loop_start(seconds(loop_start));
+            source.set_loop_end(seconds(loop_end));
+        }
 
-        if let Some(end) = end_frame {
-            let duration = (end - start_frame) as f64 / sample_rate as f64;
+        if let Some(end) = playback.end_frame {
+            let duration = seconds(end - start_frame);
             source
                 .start_with_when_and_grain_offset_and_grain_duration(0.0, start_time, duration)
-                .map_err(|e| PlayerError {
-                    message: format!("Failed to start playback: {:?}", e),
-                })?;
+                .map_err(|e| web_error(e, "Failed to start playback"))?;
         } else {
             source
                 .start_with_when_and_grain_offset(0.0, start_time)
-                .map_err(|e| PlayerError {
-                    message: format!("Failed to start playback: {:?}", e),
-                })?;
+                .map_err(|e| web_error(e, "Failed to start playback"))?;
         }
 
         Ok(source)
     }
 
+    // Glides `param` to `value` rather than jumping
+    fn ramp(&self, param: &AudioParam, value: f32) -> Result<(), PlayerError> {
+        let now = self.context.current_time();
+        param
+            .cancel_scheduled_values(now)
+            .and_then(|_| param.set_target_at_time(value, now, RAMP_SECONDS))
+            .map_err(|e| web_error(e, "Failed to schedule parameter change"))?;
+        Ok(())
+    }
+
+    fn apply_gain(&self, playback: &WebPlayback) -> Result<(), PlayerError> {
+        let gain = if playback.muted { 0.0 } else { playback.volume };
+        self.ramp(&playback.gain.gain(), gain)
+    }
+
+    // Starts a fresh source for `playback` at `frame`, keeping its range end or loop
+    fn start_source(&mut self, playback: &mut WebPlayback, frame: u64) -> Result<(), PlayerError> {
+        playback.ended.set(false);
+        let ended = playback.ended.clone();
+        let listener = playback.listener.clone();
+        let ticking = Rc::new(Cell::new(true));
+        let ended_ticking = ticking.clone();
+        let stop_frame = playback.end_frame.unwrap_or(playback.frame_count as u64);
+        // Runs on the main thread once playback plays out; deliberate stops detach it first
+        let finish: Rc<dyn Fn()> = Rc::new(move || {
+            ended.set(true);
+            ended_ticking.set(false);
+            if let Some(listener) = &listener
+                && let Ok(mut listener) = listener.try_borrow_mut()
+            {
+                listener.on_progress(stop_frame);
+                listener.on_finished();
+            }
+        });
+
+        let now = self.context.current_time();
+        if playback.rate_mode == RateMode::PreservePitch && playback.rate != 1.0 {
+            playback.stretched = Some(StretchQueue::start(playback, frame, now, finish)?);
+        } else {
+            let source = self.create_and_start_source(playback, frame)?;
+            let onended = Closure::<dyn FnMut()>::new(move || finish());
+            #[allow(deprecated)]
+            source.set_onended(Some(onended.as_ref().unchecked_ref()));
+            playback.source = Some(source);
+            playback.onended = Some(onended);
+        }
+
+        playback.start_time = now;
+        playback.start_frame = frame;
+        playback.paused_at_frame = None;
+        if let Some(listener) = &playback.listener {
+            self.start_progress(listener.clone(), ticking.clone(), playback.clock())?;
+        }
+        playback.ticking = Some(ticking);
+        Ok(())
+    }
+
+    // Reports progress once per animation frame until `ticking` is cleared
+    fn start_progress(
+        &self,
+        listener: Rc<RefCell<Box<dyn PlaybackListener>>>,
+        ticking: Rc<Cell<bool>>,
+        clock: SourceClock,
+    ) -> Result<(), PlayerError> {
+        let window = web_sys::window().ok_or_else(|| PlayerError {
+            kind: PlayerErrorKind::DeviceUnavailable,
+            message: "No window object available".to_string(),
+        })?;
+        let context = self.context.clone();
+        let tick = Rc::new(RefCell::new(None::<Closure<dyn FnMut()>>));
+        let next = tick.clone();
+        let loop_window = window.clone();
+        let mut reported_loops = 0;
+
+        *tick.borrow_mut() = Some(Closure::new(move || {
+            if !ticking.get() {
+                // Drop the closure, ending the loop
+                let _ = next.borrow_mut().take();
+                return;
+            }
+            let (frame, loops) = clock.frame_at(context.current_time());
+            if let Ok(mut listener) = listener.try_borrow_mut() {
+                for _ in reported_loops..loops {
+                    listener.on_looped();
+                }
+                reported_loops = loops;
+                listener.on_progress(frame);
+            }
+            if let Some(callback) = next.borrow().as_ref() {
+                let _ = loop_window.request_animation_frame(callback.as_ref().unchecked_ref());
+            }
+        }));
+
+        if let Some(callback) = tick.borrow().as_ref() {
+            window
+                .request_animation_frame(callback.as_ref().unchecked_ref())
+                .map_err(|e| web_error(e, "Failed to request animation frame"))?;
+        }
+        Ok(())
+    }
+
+    /// Decodes an encoded audio file held in `array_buffer`, which is detached
+    /// in the process.
+    pub async fn load_array_buffer(
+        &mut self,
+        array_buffer: js_sys::ArrayBuffer,
+    ) -> Result<WebSound, PlayerError> {
+        let decode_promise = self.context.decode_audio_data(&array_buffer)
+            .map_err(|e| web_error(e, "Failed to decode audio data"))?;
+        let audio_buffer = JsFuture::from(decode_promise)
+            .await
+            .map_err(|e| web_error(e, "Failed to decode audio"))?;
+
+        let audio_buffer: AudioBuffer = audio_buffer
+            .dyn_into()
+            .map_err(|e| web_error(e, "Invalid audio buffer type"))?;
+        // TODO: This returns the AudioContext's sample rate (typically 48000Hz), not the
+        // original file's sample rate. Need to parse audio file headers before decoding
+        // to get the actual source sample rate.
+        let sample_rate = audio_buffer.sample_rate();
+        let channels = audio_buffer.number_of_channels();
+        let frame_count = audio_buffer.length();
+
+        Ok(WebSound {
+            buffer: audio_buffer,
+            sample_rate,
+            channels,
+            frame_count,
+        })
+    }
+
     fn play_internal(
         &mut self,
         sound: &mut WebSound,
         start_frame: u64,
         end_frame: Option<u64>,
-        _listener: Option<Box<dyn PlaybackListener>>,
+        loop_range: Option<(u64, u64)>,
+        listener: Option<Box<dyn PlaybackListener>>,
     ) -> Result<WebPlayback, PlayerError> {
-        let source = self.create_and_start_source(
-            &sound.buffer,
-            start_frame,
-            end_frame,
-            sound.sample_rate,
-        )?;
+        // Ranges are checked before any node exists
+        let length = sound.frame_count as u64;
+        let end = end_frame.unwrap_or(length);
+        if start_frame > end || end > length {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!(
+                    "Range {}..{} is outside the sound's {} frames",
+                    start_frame, end, length
+                ),
+            });
+        }
+        if let Some((loop_start, loop_end)) = loop_range && loop_end > length {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!(
+                    "Loop {}..{} is outside the sound's {} frames",
+                    loop_start, loop_end, length
+                ),
+            });
+        }
+
+        let gain = self
+            .context
+            .create_gain()
+            .map_err(|e| web_error(e, "Failed to create gain node"))?;
+        let panner = self
+            .context
+            .create_stereo_panner()
+            .map_err(|e| web_error(e, "Failed to create stereo panner"))?;
+        gain.connect_with_audio_node(&panner)
+            .and_then(|_| panner.connect_with_audio_node(&self.context.destination()))
+            .map_err(|e| web_error(e, "Failed to connect to destination"))?;
 
-        Ok(WebPlayback {
-            source: Some(source),
+        let mut playback = WebPlayback {
+            source: None,
             buffer: sound.buffer.clone(),
-            start_time: self.context.current_time(),
+            start_time: 0.0,
             start_frame,
             paused_at_frame: None,
             sample_rate: sound.sample_rate,
             channels: sound.channels,
             frame_count: sound.frame_count,
             end_frame,
-        })
+            loop_range,
+            gain,
+            panner,
+            volume: 1.0,
+            muted: false,
+            rate: 1.0,
+            rate_mode: RateMode::Varispeed,
+            stretched: None,
+            listener: listener.map(|listener| Rc::new(RefCell::new(listener))),
+            ended: Rc::new(Cell::new(false)),
+            onended: None,
+            ticking: None,
+        };
+        self.start_source(&mut playback, start_frame)?;
+        playback.notify(|listener| listener.on_started());
+        Ok(playback)
+    }
+}
+
+// Maps context time to buffer frames for one started source node
+#[derive(Clone, Copy)]
+struct SourceClock {
+    start_time: f64,
+    start_frame: u64,
+    stop_frame: u64,
+    sample_rate: f32,
+    rate: f64,
+    loop_range: Option<(u64, u64)>,
+}
+
+impl SourceClock {
+    // Frame playing at context `time`, and how many times the loop has wrapped by then
+    fn frame_at(&self, time: f64) -> (u64, u64) {
+        let elapsed_seconds = (time - self.start_time).max(0.0);
+        let frame =
+            self.start_frame + (elapsed_seconds * self.sample_rate as f64 * self.rate) as u64;
+        match self.loop_range {
+            Some((loop_start, loop_end)) if frame >= loop_end => {
+                let length = loop_end - loop_start;
+                let past = frame - loop_end;
+                (loop_start + past % length, past / length + 1)
+            }
+            Some(_) => (frame, 0),
+            None => (frame.min(self.stop_frame), 0),
+        }
     }
 }
 
@@ -132,57 +577,48 @@ impl Player for WebPlayer {
     }
 
     async fn load(&mut self, source: &str) -> Result<Self::Sound, PlayerError> {
-        let request = Request::new_with_str(source).map_err(|e| PlayerError {
-            message: format!("Failed to create request: {:?}", e),
-        })?;
+        let request = Request::new_with_str(source)
+            .map_err(|e| web_error(e, "Failed to create request"))?;
         let window = web_sys::window().ok_or_else(|| PlayerError {
+            kind: PlayerErrorKind::DeviceUnavailable,
             message: "No window object available".to_string(),
         })?;
         let response = JsFuture::from(window.fetch_with_request(&request))
             .await
             .map_err(|e| PlayerError {
+                kind: PlayerErrorKind::FileNotFound,
                 message: format!("Failed to fetch '{}': {:?}", source, e),
             })?;
-        let response: Response = response.dyn_into().map_err(|e| PlayerError {
-            message: format!("Invalid response type: {:?}", e),
-        })?;
+        let response: Response = response
+            .dyn_into()
+            .map_err(|e| web_error(e, "Invalid response type"))?;
+        if !response.ok() {
+            let kind = match response.status() {
+                404 | 410 => PlayerErrorKind::FileNotFound,
+                status => PlayerErrorKind::BackendSpecific { code: status as i32 },
+            };
+            return Err(PlayerError {
+                kind,
+                message: format!("Failed to fetch '{}': HTTP {}", source, response.status()),
+            });
+        }
         let array_buffer = JsFuture::from(
-            response.array_buffer().map_err(|e| PlayerError {
-                message: format!("Failed to get array buffer: {:?}", e),
-            })?
+            response.array_buffer().map_err(|e| web_error(e, "Failed to get array buffer"))?
         )
         .await
-        .map_err(|e| PlayerError {
-            message: format!("Failed to read array buffer: {:?}", e),
-        })?;
+        .map_err(|e| web_error(e, "Failed to read array buffer"))?;
 
-        let array_buffer = js_sys::ArrayBuffer::from(array_buffer);
-        let decode_promise = self.context.decode_audio_data(&array_buffer)
-            .map_err(|e| PlayerError {
-                message: format!("Failed to decode audio data: {:?}", e),
-            })?;
-        let audio_buffer = JsFuture::from(decode_promise)
-            .await
-            .map_err(|e| PlayerError {
-                message: format!("Failed to decode audio: {:?}", e),
-            })?;
+        self.load_array_buffer(js_sys::ArrayBuffer::from(array_buffer)).await
+    }
 
-        let audio_buffer: AudioBuffer = audio_buffer.dyn_into().map_err(|e| PlayerError {
-            message: format!("Invalid audio buffer type: {:?}", e),
-        })?;
-        // TODO: This returns the AudioContext's sample rate (typically 48000Hz), not the
-        // original file's sample rate. Need to parse audio file headers before decoding
-        // to get the actual source sample rate.
-        let sample_rate = audio_buffer.sample_rate();
-        let channels = audio_buffer.number_of_channels();
-        let frame_count = audio_buffer.length();
+    async fn load_bytes(&mut self, bytes: &[u8]) -> Result<Self::Sound, PlayerError> {
+        // decodeAudioData detaches its input, so it gets its own copy outside wasm memory
+        let array = js_sys::Uint8Array::from(bytes);
+        self.load_array_buffer(array.buffer()).await
+    }
 
-        Ok(WebSound {
-            buffer: audio_buffer,
-            sample_rate,
-            channels,
-            frame_count,
-        })
+    async fn load_owned(&mut self, bytes: Vec<u8>) -> Result<Self::Sound, PlayerError> {
+        self.load_bytes(&bytes).await
     }
 
     fn play_from(
@@ -191,7 +627,7 @@ impl Player for WebPlayer {
         start_frame: u64,
         listener: Option<Self::PlaybackListener>,
     ) -> Result<Self::Playback, PlayerError> {
-        self.play_internal(sound, start_frame, None, listener)
+        self.play_internal(sound, start_frame, None, None, listener)
     }
 
     fn play_range(
@@ -201,27 +637,131 @@ impl Player for WebPlayer {
         end_frame: u64,
         listener: Option<Self::PlaybackListener>,
     ) -> Result<Self::Playback, PlayerError> {
-        self.play_internal(sound, start_frame, Some(end_frame), listener)
+        self.play_internal(sound, start_frame, Some(end_frame), None, listener)
+    }
+
+    fn play_loop(
+        &mut self,
+        sound: &mut Self::Sound,
+        loop_start: u64,
+        loop_end: u64,
+        start_frame: u64,
+        listener: Option<Self::PlaybackListener>,
+    ) -> Result<Self::Playback, PlayerError> {
+        if loop_start >= loop_end || start_frame >= loop_end {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!(
+                    "Invalid loop {}..{} starting at {}",
+                    loop_start, loop_end, start_frame
+                ),
+            });
+        }
+        self.play_internal(sound, start_frame, None, Some((loop_start, loop_end)), listener)
     }
 
     fn pause(&mut self, playback: &mut Self::Playback) -> Result<u64, PlayerError> {
-        if let Some(ref source) = playback.source {
-            let current_time = self.context.current_time();
-            let elapsed_seconds = current_time - playback.start_time;
-            let elapsed_frames = (elapsed_seconds * playback.sample_rate as f64) as u64;
-            let current_frame = playback.start_frame + elapsed_frames;
-            #[allow(deprecated)]
-            source.stop().map_err(|e| PlayerError {
-                message: format!("Failed to stop playback: {:?}", e),
-            })?;
-            playback.source = None;
+        if playback.started() {
+            let (current_frame, _) = playback.clock().frame_at(self.context.current_time());
+            if playback.ended.get() {
+                // Already finished; leave it that way
+                return Ok(current_frame);
+            }
+            playback.stop_source()?;
             playback.paused_at_frame = Some(current_frame);
+            playback.notify(|listener| listener.on_paused(current_frame));
             Ok(current_frame)
         } else {
             Ok(playback.paused_at_frame.unwrap_or(playback.start_frame))
         }
     }
 
+    fn resume(&mut self, playback: &mut Self::Playback) -> Result<(), PlayerError> {
+        if playback.ended.get() {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidState,
+                message: "Cannot resume a playback that is Finished".to_string(),
+            });
+        }
+        if playback.started() {
+            return Ok(());
+        }
+        let frame = playback.paused_at_frame.unwrap_or(playback.start_frame);
+        self.start_source(playback, frame)?;
+        playback.notify(|listener| listener.on_started());
+        Ok(())
+    }
+
+    fn seek(&mut self, playback: &mut Self::Playback, frame: u64) -> Result<(), PlayerError> {
+        if let Some(end) = playback.end_frame && frame > end {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!("Seek frame {} is past range end {}", frame, end),
+            });
+        }
+        // Source nodes cannot be repositioned, so a playing one is replaced
+        if playback.started() {
+            playback.stop_source()?;
+            self.start_source(playback, frame)
+        } else {
+            playback.paused_at_frame = Some(frame);
+            Ok(())
+        }
+    }
+
+    fn set_volume(&mut self, playback: &mut Self::Playback, volume: f32) -> Result<(), PlayerError> {
+        if !(volume >= 0.0 && volume.is_finite()) {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!("Invalid volume {}", volume),
+            });
+        }
+        playback.volume = volume;
+        self.apply_gain(playback)
+    }
+
+    fn set_muted(&mut self, playback: &mut Self::Playback, muted: bool) -> Result<(), PlayerError> {
+        playback.muted = muted;
+        self.apply_gain(playback)
+    }
+
+    fn set_pan(&mut self, playback: &mut Self::Playback, pan: f32) -> Result<(), PlayerError> {
+        if !pan.is_finite() {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!("Invalid pan {}", pan),
+            });
+        }
+        self.ramp(&playback.panner.pan(), pan.clamp(-1.0, 1.0))
+    }
+
+    fn set_playback_rate(
+        &mut self,
+        playback: &mut Self::Playback,
+        rate: f64,
+        mode: RateMode,
+    ) -> Result<(), PlayerError> {
+        if !(rate > 0.0 && rate.is_finite()) {
+            return Err(PlayerError {
+                kind: PlayerErrorKind::InvalidRange,
+                message: format!("Invalid playback rate {}", rate),
+            });
+        }
+        // As with seeking, the source and its clock are replaced at the current frame; in
+        // pitch-preserving mode, only the block around it is stretched before playing on
+        if playback.started() && !playback.ended.get() {
+            let (frame, _) = playback.clock().frame_at(self.context.current_time());
+            playback.stop_source()?;
+            playback.rate = rate;
+            playback.rate_mode = mode;
+            self.start_source(playback, frame)
+        } else {
+            playback.rate = rate;
+            playback.rate_mode = mode;
+            Ok(())
+        }
+    }
+
     fn get_metadata(&mut self, sound: &mut Self::Sound) -> Result<Metadata, PlayerError> {
         Ok(Metadata {
             sample_rate: sound.sample_rate as u32,
@@ -231,11 +771,16 @@ impl Player for WebPlayer {
     }
 
     fn get_state(&mut self, playback: &mut Self::Playback) -> Result<PlaybackState, PlayerError> {
-        // Check if we have an active source node
-        if playback.source.is_some() {
-            Ok(PlaybackState::Playing)
+        if self.context.state() == AudioContextState::Closed {
+            return Ok(PlaybackState::Invalid);
+        }
+        // A source node or stretch queue exists from start until pause, and reports the end
+        if !playback.started() {
+            Ok(PlaybackState::Paused)
+        } else if playback.ended.get() {
+            Ok(PlaybackState::Finished)
         } else {
-            Ok(PlaybackState::NotPlaying)
+            Ok(PlaybackState::Playing)
         }
     }
 }